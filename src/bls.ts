@@ -1,7 +1,7 @@
 import { fromHex } from '@dfinity/agent'
 import { concat } from '@dfinity/candid'
 
-import { bls_sign, bls_init, bls_get_key_pair } from './wasm_tools/pkg/wasm_tools.js'
+import { bls_sign, bls_init, bls_get_key_pair, bls_key_pair_from_seed, bls_verify } from './wasm_tools/pkg/wasm_tools.js'
 
 export const DER_PREFIX = fromHex(
     '308182301d060d2b0601040182dc7c0503010201060c2b0601040182dc7c05030201036100'
@@ -16,11 +16,12 @@ export class Bls {
     // public key
     publicKey: Uint8Array
 
-    async init() {
+    // Passing a seed of 32 to 64 bytes always gives the same root key
+    async init(seed?: Uint8Array) {
         //miracl embedded in wasm
         bls_init()
 
-        const keys = bls_get_key_pair()
+        const keys = seed !== undefined ? bls_key_pair_from_seed(seed) : bls_get_key_pair()
 
         this.S = keys.slice(0,48)
         // this.W = keys.slice(48)
@@ -97,4 +98,8 @@ export class Bls {
 
         return sig
     }
+
+    verify(sig: ArrayBuffer, msg: ArrayBuffer, publicKey: Uint8Array = this.publicKey): boolean {
+        return bls_verify(new Uint8Array(sig), new Uint8Array(msg), publicKey)
+    }
 }
\ No newline at end of file
@@ -1,5 +1,8 @@
 import { HashTree } from "@dfinity/agent"
 import { lebEncode } from "@dfinity/candid"
+import cbor, { Tagged } from 'cbor'
+
+import { hash_tree_build, hash_tree_digest, hash_tree_witness, hash_tree_merge } from './wasm_tools/pkg/wasm_tools.js'
 
 interface TreeLabel {
     name: ArrayBuffer,
@@ -66,78 +69,54 @@ export class Tree {
         return this.traverseTree(nextNode, path.slice(1))
     }
 
+    // Throws if a node has both a value and children, a hash tree node holds only one of them
     getHashTree(): HashTree {
-        return makeHashTree(this.node)
+        return decodeTree(hash_tree_build(cbor.encode(this.getPairs())))
     }
-}
-
 
-function makeHashTree(node: TreeNode): HashTree {
-    if (node.nodes.length > 0) {
-        const items: HashTree[] = node.nodes.map((x) => [2, x.name, makeHashTree(x.value)]);
-
-        return fork(items)
-    }
-    if (node.value !== undefined) {
-        return [3, node.value]
+    // [path, value] pairs of all values, as taken by certificate_build
+    getPairs(): [ArrayBuffer[], ArrayBuffer][] {
+        return collectPairs(this.node, [])
     }
-    return [0]
-    
 }
-function fork(items: HashTree[]): HashTree {
-    if (items.length === 1) {
-        return items[0]
-    }
-    if (items.length === 2) {
-        return [1, items[0], items[1]]
+
+function collectPairs(node: TreeNode, path: ArrayBuffer[]): [ArrayBuffer[], ArrayBuffer][] {
+    const pairs: [ArrayBuffer[], ArrayBuffer][] = []
+    if (node.value !== undefined) {
+        pairs.push([path, node.value])
     }
-    if (items.length === 3) {
-        return [1, items[0], fork(items.slice(1))]
+    for (const x of node.nodes) {
+        pairs.push(...collectPairs(x.value, [...path, x.name]))
     }
+    return pairs
+}
+
+function encodeTree(tree: HashTree): Uint8Array {
+    return cbor.encode(tree)
+}
 
-    return items[0]
+function decodeTree(data: Uint8Array): HashTree {
+    const tree = cbor.decode(data)
+    return tree instanceof Tagged ? tree.value : tree
 }
 
 // Builds tree based on path and value
 export function makeHashTreeOld(path: (Buffer | string)[], val: Buffer | string): HashTree {
-    if (typeof val === 'string') {
-        val = Buffer.from(new TextEncoder().encode(val))
-    }
-    let prev: HashTree = [3, val]
-
-    for (let item of path.reverse()) {
-        if (typeof item === 'string') {
-            item = Buffer.from(new TextEncoder().encode(item))
-        }
-
-        const treeItem = [2, item, prev]
-        prev = treeItem
-    }
-
-    return prev
+    const tree = new Tree()
+    tree.insertValue(path, val)
+    return tree.getHashTree()
 }
 
-// function clone(tree: HashTree): HashTree {
-//     let item: HashTree = [...tree]
+export function rootHash(tree: HashTree): ArrayBuffer {
+    return hash_tree_digest(encodeTree(tree)).buffer
+}
 
-//     return item
-// }
+// Prunes everything but the given paths, missing paths are proven absent
+export function witness(tree: HashTree, paths: (Buffer | string)[][]): HashTree {
+    return decodeTree(hash_tree_witness(encodeTree(tree), cbor.encode(paths)))
+}
 
+// Merges two witnesses of the same tree, throws if their root hashes differ
 export function mergeTrees(tree1: HashTree, tree2: HashTree): HashTree {
-    let item: HashTree = [0]
-
-    if (tree1[0] === 2) {
-        if (tree2[0] === 2) {
-            if (Buffer.compare(tree1[1] as Buffer, tree2[1] as Buffer) === 0) {
-                //the same label
-                const val = mergeTrees(tree1[2], tree2[2])
-                item = [2, tree1[1], val]
-            } else {
-                //label is different
-                item = [1, tree1, tree2]
-            }
-        }
-    }
-
-    return item
+    return decodeTree(hash_tree_merge(encodeTree(tree1), encodeTree(tree2)))
 }
@@ -13,20 +13,32 @@ export class IdlResult {
 class IdlBuilder {
   private readonly data: any
   private types: Record<string, IDL.ConstructType>
+  private readonly recs: Record<string, IDL.RecClass>
 
   private readonly fill: any[]
 
   constructor (data: any) {
     this.data = data
     this.types = {}
+    this.recs = {}
     this.fill = []
   }
 
   public build_idl (IDL: any): IdlResult {
+    // Recursive types are created up front so that every reference, including
+    // mutually recursive ones, resolves to the same IDL.Rec instance
+    for (const name of this.data.recs ?? []) {
+      this.recs[name] = IDL.Rec()
+    }
+
     for (const name of Object.keys(this.data.types)) {
       this.get_type(IDL, name)
     }
 
+    for (const [name, rec] of Object.entries(this.recs)) {
+      rec.fill(this.types[name])
+    }
+
     for (const item of this.fill) {
       item()
     }
@@ -86,6 +98,9 @@ class IdlBuilder {
 
     if (data === null) {
       idl = IDL.Null
+    } else if (data.Prim !== undefined) {
+      // Primitive types carrying a doc comment
+      idl = this.get_idl(IDL, data.Prim)
     } else if (data.Record !== undefined) {
       idl = this.get_record(IDL, data.Record)
     } else if (data.Tuple !== undefined) {
@@ -93,7 +108,8 @@ class IdlBuilder {
     } else if (data.Func !== undefined) {
       const args = this.get_idls(IDL, data.Func.args)
       const rets = this.get_idls(IDL, data.Func.rets)
-      const modes = data.Func.modes.map((x: string) => x.toLowerCase())
+      // @dfinity/candid 0.15 only knows query and oneway, composite queries are called like queries
+      const modes = data.Func.modes.map((x: string) => x === 'composite_query' ? 'query' : x)
 
       idl = IDL.Func(args, rets, modes)
     } else if (data.Vec !== undefined) {
@@ -102,6 +118,8 @@ class IdlBuilder {
       idl = this.get_variant(IDL, data.Variant)
     } else if (data.Opt !== undefined) {
       idl = IDL.Opt(this.get_idl(IDL, data.Opt))
+    } else if (data.Rec !== undefined) {
+      idl = this.recs[data.Rec]
     } else if (data.Var !== undefined) {
       if (this.types[data.Var] !== undefined) {
         idl = this.types[data.Var]
@@ -125,6 +143,9 @@ class IdlBuilder {
         case 'nat8':
           idl = IDL.Nat8
           break
+        case 'nat16':
+          idl = IDL.Nat16
+          break
         case 'nat32':
           idl = IDL.Nat32
           break
@@ -140,9 +161,36 @@ class IdlBuilder {
         case 'int':
           idl = IDL.Int
           break
+        case 'int8':
+          idl = IDL.Int8
+          break
+        case 'int16':
+          idl = IDL.Int16
+          break
+        case 'int32':
+          idl = IDL.Int32
+          break
+        case 'int64':
+          idl = IDL.Int64
+          break
+        case 'float32':
+          idl = IDL.Float32
+          break
+        case 'float64':
+          idl = IDL.Float64
+          break
         case 'bool':
           idl = IDL.Bool
           break
+        case 'null':
+          idl = IDL.Null
+          break
+        case 'empty':
+          idl = IDL.Empty
+          break
+        case 'reserved':
+          idl = IDL.Reserved
+          break
         default:
           break
       }
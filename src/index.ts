@@ -6,4 +6,4 @@ export { TestContext, getGlobalTestContext } from './test_context'
 export { LedgerHelper } from './helpers/ledger_helper'
 export { ActorSubclass } from './mock_actor'
 
-export { getAccount, hexToBytes, u64IntoCanisterId, canisterIdIntoU64, u64IntoPrincipalId } from './utils'
+export { getAccount, hexToBytes, u64IntoCanisterId, canisterIdIntoU64, u64IntoPrincipalId, principalFromPublicKey, principalKind, encodeIcrcAccount, decodeIcrcAccount } from './utils'
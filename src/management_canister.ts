@@ -11,6 +11,8 @@ import crypto from 'crypto'
 import { CanisterInstallMode } from "@dfinity/agent"
 import { WasmCanister } from "./wasm_canister"
 import { loadWasm } from "./instrumentation"
+import cbor from 'cbor'
+import { ecdsa_public_key, schnorr_public_key, sign_with_ecdsa, sign_with_schnorr } from './wasm_tools/pkg/wasm_tools.js'
 
 const { OptClass, Rec } = require("@dfinity/candid/lib/cjs/idl")
 const leb128_1 = require('@dfinity/candid/lib/cjs/utils/leb128')
@@ -51,6 +53,40 @@ interface ProvisionalArgs {
 //   settings: CanisterSettings[],
 // }
 
+interface EcdsaKeyId {
+  name: string,
+  curve: { secp256k1: null }
+}
+
+interface EcdsaPublicKeyArgs {
+  key_id: EcdsaKeyId,
+  canister_id: Principal[],
+  derivation_path: Uint8Array[]
+}
+
+interface SignWithEcdsaArgs {
+  key_id: EcdsaKeyId,
+  derivation_path: Uint8Array[],
+  message_hash: Uint8Array
+}
+
+interface SchnorrKeyId {
+  name: string,
+  algorithm: { bip340secp256k1: null } | { ed25519: null }
+}
+
+interface SchnorrPublicKeyArgs {
+  key_id: SchnorrKeyId,
+  canister_id: Principal[],
+  derivation_path: Uint8Array[]
+}
+
+interface SignWithSchnorrArgs {
+  key_id: SchnorrKeyId,
+  derivation_path: Uint8Array[],
+  message: Uint8Array
+}
+
 interface CanisterCreateResult {
   canister_id: Principal
 }
@@ -62,6 +98,18 @@ class CustomError implements Error {
   code: number
 }
 
+// wasm_tools throws plain strings, rethrown as errors the caller gets as a canister reject
+function rejectOnError<T>(f: () => T): T {
+  try {
+    return f()
+  } catch (e) {
+    const err = new Error(String(e)) as CustomError
+    err.code = RejectionCode.CanisterReject
+
+    throw err
+  }
+}
+
 export class ManagementCanister implements Canister {
   private context: ReplicaContext
 
@@ -197,4 +245,36 @@ export class ManagementCanister implements Canister {
       throw new Error('Canister not found')
     }
   }
-}
\ No newline at end of file
+
+  ecdsa_public_key(msg: Message, args: EcdsaPublicKeyArgs): { public_key: Uint8Array, chain_code: Uint8Array } {
+    const canister_id = args.canister_id[0] ?? msg.sender
+    const path = cbor.encode(args.derivation_path.map(x => Buffer.from(x)))
+    const key = rejectOnError(() => ecdsa_public_key(args.key_id.name, canister_id.toUint8Array(), path))
+
+    return { public_key: key.slice(0, 33), chain_code: key.slice(33) }
+  }
+
+  sign_with_ecdsa(msg: Message, args: SignWithEcdsaArgs): { signature: Uint8Array } {
+    const path = cbor.encode(args.derivation_path.map(x => Buffer.from(x)))
+    const signature = rejectOnError(() => sign_with_ecdsa(args.key_id.name, msg.sender.toUint8Array(), path, new Uint8Array(args.message_hash)))
+
+    return { signature }
+  }
+
+  schnorr_public_key(msg: Message, args: SchnorrPublicKeyArgs): { public_key: Uint8Array, chain_code: Uint8Array } {
+    const canister_id = args.canister_id[0] ?? msg.sender
+    const path = cbor.encode(args.derivation_path.map(x => Buffer.from(x)))
+    const algorithm = Object.keys(args.key_id.algorithm)[0]
+    const key = rejectOnError(() => schnorr_public_key(algorithm, args.key_id.name, canister_id.toUint8Array(), path))
+
+    return { public_key: key.slice(0, key.length - 32), chain_code: key.slice(key.length - 32) }
+  }
+
+  sign_with_schnorr(msg: Message, args: SignWithSchnorrArgs): { signature: Uint8Array } {
+    const path = cbor.encode(args.derivation_path.map(x => Buffer.from(x)))
+    const algorithm = Object.keys(args.key_id.algorithm)[0]
+    const signature = rejectOnError(() => sign_with_schnorr(algorithm, args.key_id.name, msg.sender.toUint8Array(), path, new Uint8Array(args.message)))
+
+    return { signature }
+  }
+}
@@ -58,6 +58,10 @@ export const idlFactory = ({ IDL }) => {
     'compute_allocation' : IDL.Opt(IDL.Nat),
   });
   const ecdsa_curve = IDL.Variant({ 'secp256k1' : IDL.Null });
+  const schnorr_algorithm = IDL.Variant({
+    'bip340secp256k1' : IDL.Null,
+    'ed25519' : IDL.Null,
+  });
   const http_header = IDL.Record({ 'value' : IDL.Text, 'name' : IDL.Text });
   const http_response = IDL.Record({
     'status' : IDL.Nat,
@@ -192,6 +196,25 @@ export const idlFactory = ({ IDL }) => {
         [],
       ),
     'raw_rand' : IDL.Func([], [IDL.Vec(IDL.Nat8)], []),
+    'schnorr_public_key' : IDL.Func(
+        [
+          IDL.Record({
+            'key_id' : IDL.Record({
+              'algorithm' : schnorr_algorithm,
+              'name' : IDL.Text,
+            }),
+            'canister_id' : IDL.Opt(canister_id),
+            'derivation_path' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+          }),
+        ],
+        [
+          IDL.Record({
+            'public_key' : IDL.Vec(IDL.Nat8),
+            'chain_code' : IDL.Vec(IDL.Nat8),
+          }),
+        ],
+        [],
+      ),
     'sign_with_ecdsa' : IDL.Func(
         [
           IDL.Record({
@@ -203,6 +226,20 @@ export const idlFactory = ({ IDL }) => {
         [IDL.Record({ 'signature' : IDL.Vec(IDL.Nat8) })],
         [],
       ),
+    'sign_with_schnorr' : IDL.Func(
+        [
+          IDL.Record({
+            'key_id' : IDL.Record({
+              'algorithm' : schnorr_algorithm,
+              'name' : IDL.Text,
+            }),
+            'derivation_path' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+            'message' : IDL.Vec(IDL.Nat8),
+          }),
+        ],
+        [IDL.Record({ 'signature' : IDL.Vec(IDL.Nat8) })],
+        [],
+      ),
     'start_canister' : IDL.Func(
         [IDL.Record({ 'canister_id' : canister_id })],
         [],
@@ -1,22 +1,28 @@
 import { Principal } from '@dfinity/principal'
-import { WasmCanister } from './wasm_canister'
+import { WasmCanister, type CandidFiles } from './wasm_canister'
 
 import debug from 'debug'
 import { CallSource, CallStatus, CallType, Message, RejectionCode } from './call_context'
 import { u64IntoCanisterId } from './utils'
+import { CANISTERS_PER_SUBNET } from './topology'
 import { ManagementCanister } from './management_canister'
 import { Canister, WasmModule } from './canister'
 const log = debug('lightic:replica')
 
 export interface InstallCanisterArgs {
   initArgs?: ArrayBuffer,
-  candid?: string,
+  candid?: string | CandidFiles,
   id?: string,
-  caller?: Principal
+  caller?: Principal,
+  // Subnet to allocate the canister id on, the root subnet by default
+  subnet?: number
 }
 
 export class ReplicaContext {
-  private last_id: bigint
+  // Number of subnets canister ids are allocated on
+  private subnets: number
+  // Last allocated canister id of every subnet, relative to its range
+  private last_ids: Record<number, bigint>
   private canisters: Record<string, Canister>
   // private msg_log: Message[]
 
@@ -27,9 +33,10 @@ export class ReplicaContext {
   //How many canisters at once should be freed
   private freeCanisters: number
 
-  constructor() {
+  constructor(subnets: number = 1) {
+    this.subnets = subnets
     this.canisters = {}
-    this.last_id = 0n
+    this.last_ids = {}
     this.messages = {}
 
     this.canisters['aaaaa-aa'] = new ManagementCanister(this)
@@ -139,9 +146,17 @@ export class ReplicaContext {
     return canister.get_module_hash();
   }
 
-  get_canister_id(): Principal {
-    const id = u64IntoCanisterId(this.last_id)
-    this.last_id += 1n
+  get_canister_id(subnet: number = 0): Principal {
+    if (!Number.isInteger(subnet) || subnet < 0 || subnet >= this.subnets) {
+      throw new Error('Subnet ' + subnet + ' does not exist, the replica has ' + this.subnets + ' subnets')
+    }
+
+    const last_id = this.last_ids[subnet] ?? 0n
+    if (last_id >= CANISTERS_PER_SUBNET) {
+      throw new Error('All ' + CANISTERS_PER_SUBNET + ' canister ids of subnet ' + subnet + ' are taken')
+    }
+    const id = u64IntoCanisterId(BigInt(subnet) * CANISTERS_PER_SUBNET + last_id)
+    this.last_ids[subnet] = last_id + 1n
     return id
   }
 
@@ -149,7 +164,7 @@ export class ReplicaContext {
     let idPrin: Principal | undefined
 
     if (params === undefined || params.id === undefined) {
-      idPrin = this.get_canister_id()
+      idPrin = this.get_canister_id(params?.subnet)
     } else {
       if (this.canisters[params.id] !== undefined) {
         throw new Error('Canister with id ' + params.id + ' is already installed')
@@ -179,7 +194,7 @@ export class ReplicaContext {
     let idPrin: Principal | undefined
 
     if (params.id === undefined) {
-      idPrin = this.get_canister_id()
+      idPrin = this.get_canister_id(params.subnet)
     } else {
       if (this.canisters[params.id] !== undefined) {
         throw new Error('Canister with id ' + params.id + ' is already installed')
@@ -228,7 +243,7 @@ export class ReplicaContext {
   // Removes all canisters from replica
   clean(): void {
     this.canisters = {}
-    this.last_id = 0n
+    this.last_ids = {}
     this.messages = {}
     this.canisters['aaaaa-aa'] = new ManagementCanister(this)
 
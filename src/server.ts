@@ -7,11 +7,13 @@ import cbor, { Tagged } from 'cbor'
 import { ReplicaContext } from './replica_context';
 import { CallSource, CallStatus, CallType, Message } from './call_context';
 import { Principal } from '@dfinity/principal';
-import { ReadStateResponse, requestIdOf, toHex, concat, HashTree, reconstruct } from '@dfinity/agent';
+import { ReadStateResponse, fromHex, toHex } from '@dfinity/agent';
 
 import { Tree } from './hash_tree';
 
 import { Bls } from './bls';
+import { Topology } from './topology';
+import { certificate_build, request_id, validate_envelope } from './wasm_tools/pkg/wasm_tools.js';
 
 import {Command} from 'commander'
 import path from 'path';
@@ -19,13 +21,16 @@ import path from 'path';
 const program = new Command();
 program.option('--p, --port <number>','Specifies port on which http server will be started');
 program.option('--c, --clean','Cleans up the DFX state before starting the server');
+program.option('--s, --subnets <number>','Number of subnets, canisters of subnets other than the first get delegated certificates');
+program.option('--seed <hex>','Hex seed of 32 to 64 bytes the root key is derived from, defaults to LIGHTIC_SEED or a random key');
 program.parse(process.argv);
 
 const options = program.opts();
 
 const bls = new Bls()
+const topology = new Topology(bls)
 
-const context = new ReplicaContext();
+const context = new ReplicaContext(Number(options.subnets ?? 1));
 
 const app = express();
 const port = options.port ?? 8001;
@@ -62,6 +67,21 @@ interface CallRequest<T> {
     sender_sig: Buffer
 }
 
+// Validates the envelope and its signature as a replica would, on rejection responds with it and returns false
+function validateEnvelope(res: express.Response, body: Uint8Array, requestType: string, canisterId: string): boolean {
+    const now = BigInt(Date.now()) * 1_000_000n
+    try {
+        const requestSender = (id: Uint8Array) => context.get_message(toHex(id))?.sender.toUint8Array()
+        validate_envelope(body, requestType, Principal.from(canisterId).toUint8Array(), bls.derPublicKey, now, requestSender)
+        return true
+    } catch (e) {
+        const rejection = JSON.parse(e as string)
+        res.status(rejection.status)
+        res.send(rejection.message)
+        return false
+    }
+}
+
 app.use(express.raw())
 app.use(express.json())
 
@@ -77,51 +97,17 @@ app.post('/api/v2/canister/:canisterId/query', (req, res) => {
         body.push(chunk)
     }).on('end', async () => {
 
-        const tag = cbor.decode(body[0] as Buffer)
-        const data = tag.value as CallRequest<QueryContent>
-        const content = data.content
-
-        if (content.sender === undefined) {
-            res.status(400)
-            res.send('Missing sender')
-
-            return
-        }
-
-        if (content.arg === undefined) {
-            res.status(400)
-            res.send('Missing arg')
-
-            return
-        }
-
-        if (content.method_name === undefined) {
-            res.status(400)
-            res.send('Missing arg')
-
+        if (!validateEnvelope(res, body[0], 'query', req.params.canisterId)) {
             return
         }
 
-
-        if (content.canister_id === undefined) {
-            res.status(400)
-            res.send('Missing canister_id')
-
-            return
-        }
-
-        if (data.content.request_type !== 'query') {
-            res.status(400)
-            res.send('Invalid request type')
-
-            return
-        }
-
-        
+        const tag = cbor.decode(body[0] as Buffer)
+        const data = tag.value as CallRequest<QueryContent>
+        const content = data.content
 
         const sender = Principal.fromUint8Array(content.sender)
         const canister_id = Principal.fromUint8Array(content.canister_id)
-        const reqId = toHex(requestIdOf(data.content))
+        const reqId = toHex(request_id(cbor.encode(data.content)))
 
         const msg = new Message({
             id: reqId,
@@ -195,37 +181,13 @@ app.post('/api/v2/canister/:canisterId/call', (req, res) => {
             pos += item.byteLength
         }
 
-        const tag = cbor.decode(rawData)
-        const data = tag.value as CallRequest<CallContent>
-        const content = data.content
-
-        if (content.sender === undefined) {
-            res.status(400)
-            res.send('Missing sender')
-
-            return
-        }
-
-        if (content.method_name === undefined) {
-            res.status(400)
-            res.send('Missing method name')
-
-            return
-        }
-
-        if (content.canister_id === undefined) {
-            res.status(400)
-            res.send('Missing canister_id')
-
+        if (!validateEnvelope(res, rawData, 'call', canisterId)) {
             return
         }
 
-        if (data.content.request_type !== 'call') {
-            res.status(400)
-            res.send('Invalid request type')
-
-            return
-        }
+        const tag = cbor.decode(rawData)
+        const data = tag.value as CallRequest<CallContent>
+        const content = data.content
 
         if (tag.tag !== 55799) {
             res.send({});
@@ -241,7 +203,7 @@ app.post('/api/v2/canister/:canisterId/call', (req, res) => {
                 res.status(404)
                 res.send('Canister not found: ' + canisterId)
             } else {
-                const reqId = toHex(requestIdOf(data.content))
+                const reqId = toHex(request_id(cbor.encode(data.content)))
 
                 if (content.request_type === 'call') {
                     const msg = new Message({
@@ -275,18 +237,9 @@ app.post('/api/v2/canister/:canisterId/call', (req, res) => {
 
 });
 
-export async function getReadResponse(bls: Bls, hashTree: HashTree): Promise<Buffer> {
-    const rootHash = await reconstruct(hashTree)
-
-    const msg2 = concat(bls.domain_sep('ic-state-root'), rootHash);
-    const sig = await bls.sign(msg2)
-
-    const cert = {
-        tree: hashTree,
-        signature: Buffer.from(sig)
-    }
-    const certTagged = new Tagged(55799, cert);
-    const certEncoded = cbor.encode(certTagged);
+export async function getReadResponse(bls: Bls, tree: Tree, delegation?: Uint8Array): Promise<Buffer> {
+    const pairs = cbor.encode(new Tagged(55799, tree.getPairs()))
+    const certEncoded = Buffer.from(certificate_build(pairs, bls.S, delegation))
 
     const resp: ReadStateResponse = {
         certificate: certEncoded
@@ -303,32 +256,14 @@ app.post('/api/v2/canister/:canisterId/read_state', (req, res) => {
         body.push(chunk)
     }).on('end', async () => {
 
-        const tag = cbor.decode(body[0] as Buffer)
-        const data = tag.value as CallRequest<ReadStateContent>
-
-        if (data.content.request_type !== 'read_state') {
-            res.status(400)
-            res.send('Invalid request type')
-
+        if (!validateEnvelope(res, body[0], 'read_state', req.params.canisterId)) {
             return
         }
 
+        const tag = cbor.decode(body[0] as Buffer)
+        const data = tag.value as CallRequest<ReadStateContent>
         const paths = data.content.paths
 
-        if (data.content.sender === undefined) {
-            res.status(400)
-            res.send('Missing sender')
-
-            return
-        }
-
-        if (paths === undefined || paths === null) {
-            res.status(400)
-            res.send('Missing paths')
-
-            return
-        }
-
         const tree = new Tree();
 
         for (const path of paths) {
@@ -375,10 +310,11 @@ app.post('/api/v2/canister/:canisterId/read_state', (req, res) => {
         }
 
         // Every tree will have time entry
-        tree.insertValue(['time'], process.hrtime.bigint())
+        const time = BigInt(Date.now()) * 1_000_000n
+        tree.insertValue(['time'], time)
 
-        const treeHash = tree.getHashTree()
-        const resp = await getReadResponse(bls, treeHash)
+        const subnet = topology.get_subnet(Principal.from(req.params.canisterId))
+        const resp = await getReadResponse(subnet.bls, tree, topology.get_delegation(subnet, time))
         res.send(resp)
     })
 });
@@ -402,7 +338,9 @@ app.get('/api/v2/status', (req, res) => {
 
 
 async function run() {
-    await bls.init()
+    const seed = options.seed ?? process.env.LIGHTIC_SEED
+    await bls.init(seed !== undefined ? new Uint8Array(fromHex(seed)) : undefined)
+    await topology.init(Number(options.subnets ?? 1))
 
     if (options.clean) {
         //remove file if exists ~/.local/share/dfx/network/local/wallets.json
@@ -0,0 +1,51 @@
+import { Principal } from '@dfinity/principal'
+import { lebEncode } from '@dfinity/candid'
+
+import { Bls } from './bls'
+import { subnet_seed, subnet_of_canister, subnet_delegation } from './wasm_tools/pkg/wasm_tools.js'
+
+// Size of the canister id range of every subnet, `CANISTERS_PER_SUBNET` in topology.rs
+export const CANISTERS_PER_SUBNET = 1n << 20n
+
+export interface Subnet {
+    index: number
+    bls: Bls
+}
+
+// Subnets of the replica, subnet 0 is the root subnet signing with the root key
+export class Topology {
+    subnets: Subnet[]
+
+    constructor(root: Bls) {
+        this.subnets = [{ index: 0, bls: root }]
+    }
+
+    // Adds subnets until there are `count` of them, keys follow from the root key
+    async init(count: number) {
+        const root = this.subnets[0].bls
+
+        for (let index = this.subnets.length; index < count; index++) {
+            const bls = new Bls()
+            await bls.init(subnet_seed(root.S, BigInt(index)))
+
+            this.subnets.push({ index, bls })
+        }
+    }
+
+    get_subnet(canisterId: Principal): Subnet {
+        const index = subnet_of_canister(canisterId.toUint8Array())
+        const subnet = index !== undefined ? this.subnets[Number(index)] : undefined
+
+        return subnet ?? this.subnets[0]
+    }
+
+    // CBOR delegation of the subnet signed by the root key, none for the root subnet
+    get_delegation(subnet: Subnet, time: bigint): Uint8Array | undefined {
+        if (subnet.index === 0) {
+            return undefined
+        }
+
+        const root = this.subnets[0].bls
+        return subnet_delegation(root.S, subnet.bls.publicKey, BigInt(subnet.index), new Uint8Array(lebEncode(time)))
+    }
+}
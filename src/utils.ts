@@ -1,17 +1,13 @@
 import { AccountIdentifier, SubAccount } from '@dfinity/nns'
 import { Principal } from '@dfinity/principal'
+import { account_identifier, icrc1_account_from_text, icrc1_account_to_text, principal_canister_id, principal_canister_number, principal_from_public_key, principal_kind } from './wasm_tools/pkg/wasm_tools.js'
 
-export function canisterIdIntoU64 (canisterId: Principal): bigint {
-  const bytes = canisterId.toUint8Array().slice(0, 8)
-
-  let num = 0n
-
-  for (const byte of bytes) {
-    num = num << 4n
-    num += BigInt(byte)
-  }
+// Canister id 0, the start of the range canister ids are counted from
+const FIRST_CANISTER_ID = Principal.fromText('rwlgt-iiaaa-aaaaa-aaaaa-cai').toUint8Array()
 
-  return num
+// Number of a canister id, throws for principals that are not canister ids
+export function canisterIdIntoU64 (canisterId: Principal): bigint {
+  return principal_canister_number(canisterId.toUint8Array())
 }
 
 export function u64IntoPrincipalId (num: bigint): Principal {
@@ -24,15 +20,17 @@ export function u64IntoPrincipalId (num: bigint): Principal {
 }
 
 export function u64IntoCanisterId (num: bigint): Principal {
-  const bytes = new Uint8Array(10)
-
-  // todo: conversion of number to byte array
+  return Principal.fromUint8Array(principal_canister_id(FIRST_CANISTER_ID, num))
+}
 
-  bytes[7] = Number(num)
-  bytes[8] = 1
-  bytes[9] = 1
+// Self-authenticating principal of a DER encoded public key
+export function principalFromPublicKey (derKey: Uint8Array): Principal {
+  return Principal.fromUint8Array(principal_from_public_key(derKey))
+}
 
-  return Principal.fromUint8Array(bytes)
+// One of management, opaque, self_authenticating, derived, anonymous, reserved or unknown
+export function principalKind (principal: Principal): string {
+  return JSON.parse(principal_kind(principal.toUint8Array()))
 }
 
 // Convert a hex string to a byte array
@@ -46,10 +44,20 @@ export function hexToBytes (hex: string): number[] {
 
 export function getAccount (principal: Principal, no: number): AccountIdentifier {
   const subAccount = SubAccount.fromID(no)
-  const accountIdentifier = AccountIdentifier.fromPrincipal({
-    principal,
-    subAccount
-  })
 
-  return accountIdentifier
+  return AccountIdentifier.fromHex(account_identifier(principal.toUint8Array(), subAccount.toUint8Array()))
+}
+
+// ICRC-1 textual form of an account, the owner alone for the default subaccount
+export function encodeIcrcAccount (owner: Principal, subaccount?: Uint8Array): string {
+  return icrc1_account_to_text(owner.toUint8Array(), subaccount)
+}
+
+export function decodeIcrcAccount (text: string): { owner: Principal, subaccount?: Uint8Array } {
+  const account = JSON.parse(icrc1_account_from_text(text))
+
+  return {
+    owner: Principal.fromText(account.owner),
+    subaccount: account.subaccount !== null ? Uint8Array.from(Buffer.from(account.subaccount, 'hex')) : undefined
+  }
 }
@@ -3,7 +3,7 @@ import { Principal } from '@dfinity/principal'
 import { buildIdl, type IdlResult } from './idl_builder'
 import { CallSource, CallStatus, CallType, Message, RejectionCode } from './call_context'
 import { type ReplicaContext } from './replica_context'
-import { parse_candid } from './wasm_tools/pkg/wasm_tools'
+import { parse_candid, parse_candid_with_imports } from './wasm_tools/pkg/wasm_tools'
 import { Canister, WasmModule } from './canister'
 import debug from 'debug'
 import { Ic0 } from './ic0'
@@ -11,6 +11,12 @@ import { hexToBytes } from './utils'
 
 const log = debug('lightic:canister')
 
+// Candid interface split over several files, `root` is the file with the service
+export interface CandidFiles {
+  files: Record<string, string>
+  root: string
+}
+
 export class CanisterState {
   replica: ReplicaContext
   canister: Canister
@@ -119,7 +125,7 @@ export class WasmCanister implements Canister {
     }
   }
 
-  async install_module_candid(module: WasmModule, initArgs: any, sender: Principal, candidSpec?: string) {
+  async install_module_candid(module: WasmModule, initArgs: any, sender: Principal, candidSpec?: string | CandidFiles) {
     // this.module = module
 
     // const imports = WebAssembly.Module.imports(module.module)
@@ -136,7 +142,9 @@ export class WasmCanister implements Canister {
       candidSpec = await this.get_candid()
     }
 
-    const jsonCandid = parse_candid(candidSpec)
+    const jsonCandid = typeof candidSpec === 'string'
+      ? parse_candid(candidSpec)
+      : parse_candid_with_imports(JSON.stringify(candidSpec.files), candidSpec.root)
     const candid = JSON.parse(jsonCandid)
     this.candid = candid
     this.idl = buildIdl(IDL, candid)
@@ -217,9 +225,17 @@ export class WasmCanister implements Canister {
         log('Error on RejectCallback of {}', e)
       }
     } else {
-      const method = msg.getMethodName()
+      let method = msg.getMethodName()
       let func = this.instance.exports[method] as any
 
+      if (func === undefined && msg.type === CallType.Query) {
+        const composite = 'canister_composite_query ' + msg.method
+        if (this.instance.exports[composite] !== undefined) {
+          method = composite
+          func = this.instance.exports[method] as any
+        }
+      }
+
       if (msg.source === CallSource.InterCanister) {
         if (func === undefined) {
           func = this.methods[method]?.func;
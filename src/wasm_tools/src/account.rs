@@ -0,0 +1,232 @@
+//! Ledger accounts: account identifiers and the textual encoding of ICRC-1
+//! accounts, like `spec_test/src/account_identifier.rs`.
+
+use serde::Serialize;
+use sha2::{Digest, Sha224};
+use wasm_bindgen::prelude::*;
+
+use crate::principal::{self, base32};
+
+pub const SUB_ACCOUNT_ZERO: [u8; 32] = [0; 32];
+static ACCOUNT_DOMAIN_SEPERATOR: &[u8] = b"\x0Aaccount-id";
+
+/// 28 byte hash of an account, its canonical form is the hex of the CRC32 of
+/// the hash followed by the hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountIdentifier {
+    pub hash: [u8; 28],
+}
+
+impl AccountIdentifier {
+    pub fn new(account: &[u8], sub_account: Option<[u8; 32]>) -> AccountIdentifier {
+        let mut hash = Sha224::new();
+        hash.update(ACCOUNT_DOMAIN_SEPERATOR);
+        hash.update(account);
+        hash.update(sub_account.unwrap_or(SUB_ACCOUNT_ZERO));
+
+        AccountIdentifier {
+            hash: hash.finalize().into(),
+        }
+    }
+
+    /// Reads the 64 characters of the canonical form, checking the checksum, or
+    /// the 56 characters of the hash.
+    pub fn from_hex(hex_str: &str) -> Result<AccountIdentifier, String> {
+        let bytes = hex::decode(hex_str).map_err(|e| e.to_string())?;
+
+        match bytes.len() {
+            32 => {
+                let account_id = AccountIdentifier {
+                    hash: bytes[4..].try_into().unwrap(),
+                };
+                if account_id.generate_checksum() != bytes[..4] {
+                    return Err(format!("Checksum of {hex_str} does not match"));
+                }
+                Ok(account_id)
+            }
+            28 => Ok(AccountIdentifier {
+                hash: bytes.try_into().unwrap(),
+            }),
+            _ => Err(format!(
+                "{} has a length of {} but we expected a length of 64 or 56",
+                hex_str,
+                hex_str.len()
+            )),
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_vec())
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        [&self.generate_checksum()[..], &self.hash[..]].concat()
+    }
+
+    pub fn generate_checksum(&self) -> [u8; 4] {
+        crc32fast::hash(&self.hash).to_be_bytes()
+    }
+}
+
+fn parse_sub_account(bytes: Option<Vec<u8>>) -> Result<Option<[u8; 32]>, String> {
+    bytes
+        .map(|b| b.try_into())
+        .transpose()
+        .map_err(|_| "Subaccounts are 32 bytes long".to_string())
+}
+
+/// ICRC-1 account text: the owner alone for the default subaccount, otherwise
+/// `owner-checksum.subaccount` with the subaccount in hex without leading
+/// zeros and the base32 CRC32 of owner and subaccount as checksum.
+pub fn to_text(owner: &[u8], sub_account: Option<[u8; 32]>) -> String {
+    let owner_text = principal::to_text(owner);
+    let sub_account = match sub_account {
+        Some(s) if s != SUB_ACCOUNT_ZERO => s,
+        _ => return owner_text,
+    };
+
+    let checksum = crc32fast::hash(&[owner, &sub_account].concat()).to_be_bytes();
+    let hex = hex::encode(sub_account);
+    format!(
+        "{owner_text}-{}.{}",
+        base32(&checksum),
+        hex.trim_start_matches('0')
+    )
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Account {
+    /// Textual form of the owner
+    pub owner: String,
+    /// Hex of the subaccount, none for the default one
+    pub subaccount: Option<String>,
+}
+
+pub fn from_text(text: &str) -> Result<Account, String> {
+    let Some((rest, hex_str)) = text.split_once('.') else {
+        principal::from_text(text)?;
+        return Ok(Account {
+            owner: text.to_string(),
+            subaccount: None,
+        });
+    };
+
+    let (owner_text, _) = rest
+        .rsplit_once('-')
+        .ok_or_else(|| format!("Missing checksum in account {text}"))?;
+    let owner = principal::from_text(owner_text)?;
+
+    if hex_str.is_empty() || hex_str.starts_with('0') || hex_str.len() > 64 {
+        return Err(format!("Subaccount of {text} is not in its shortest form"));
+    }
+    let mut sub_account = SUB_ACCOUNT_ZERO;
+    let bytes = hex::decode(format!("{hex_str:0>64}")).map_err(|e| e.to_string())?;
+    sub_account.copy_from_slice(&bytes);
+
+    if to_text(&owner, Some(sub_account)) != text {
+        return Err(format!("Invalid checksum in account {text}"));
+    }
+
+    Ok(Account {
+        owner: owner_text.to_string(),
+        subaccount: Some(hex::encode(sub_account)),
+    })
+}
+
+/// Hex of the account identifier of `principal` and `sub_account`.
+#[wasm_bindgen]
+pub fn account_identifier(
+    principal: &[u8],
+    sub_account: Option<Vec<u8>>,
+) -> Result<String, String> {
+    Ok(AccountIdentifier::new(principal, parse_sub_account(sub_account)?).to_hex())
+}
+
+/// Canonical hex of an account identifier given in hex, with or without its
+/// checksum.
+#[wasm_bindgen]
+pub fn account_identifier_from_hex(hex_str: &str) -> Result<String, String> {
+    Ok(AccountIdentifier::from_hex(hex_str)?.to_hex())
+}
+
+#[wasm_bindgen]
+pub fn icrc1_account_to_text(owner: &[u8], sub_account: Option<Vec<u8>>) -> Result<String, String> {
+    Ok(to_text(owner, parse_sub_account(sub_account)?))
+}
+
+/// JSON of the owner and subaccount of an ICRC-1 account text.
+#[wasm_bindgen]
+pub fn icrc1_account_from_text(text: &str) -> Result<String, String> {
+    serde_json::to_string(&from_text(text)?).map_err(|x| format!("{x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Default account of rrkah-fqaaa-aaaaa-aaaaq-cai
+    const ACCOUNT_ID: &str = "082ecf2e3f647ac600f43f38a68342fba5b8e68b085f02592b77f39808a8d2b5";
+
+    #[test]
+    fn account_identifiers() -> Result<(), String> {
+        let owner = principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai")?;
+        let hex_str = account_identifier(&owner, None)?;
+        assert_eq!(hex_str, ACCOUNT_ID);
+        assert_eq!(account_identifier(&owner, Some(vec![0; 32]))?, hex_str);
+        assert!(account_identifier(&owner, Some(vec![0; 31])).is_err());
+
+        let mut sub_account = [0; 32];
+        sub_account[31] = 1;
+        assert_ne!(
+            AccountIdentifier::new(&owner, Some(sub_account)).to_hex(),
+            hex_str
+        );
+
+        assert_eq!(account_identifier_from_hex(&hex_str)?, hex_str);
+        assert_eq!(account_identifier_from_hex(&hex_str[8..])?, hex_str);
+        let wrong = format!("00000000{}", &hex_str[8..]);
+        assert!(account_identifier_from_hex(&wrong).is_err());
+        assert!(account_identifier_from_hex("abcd").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn icrc1_accounts() -> Result<(), String> {
+        let owner_text = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+        let owner = principal::from_text(owner_text)?;
+        let sub_account: Vec<u8> = (1..=32).collect();
+
+        let text = icrc1_account_to_text(&owner, Some(sub_account.clone()))?;
+        assert_eq!(
+            text,
+            format!("{owner_text}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20")
+        );
+        assert_eq!(
+            from_text(&text)?,
+            Account {
+                owner: owner_text.to_string(),
+                subaccount: Some(hex::encode(&sub_account)),
+            }
+        );
+
+        assert_eq!(icrc1_account_to_text(&owner, None)?, owner_text);
+        assert_eq!(
+            icrc1_account_to_text(&owner, Some(vec![0; 32]))?,
+            owner_text
+        );
+        assert_eq!(from_text(owner_text)?.subaccount, None);
+
+        let mut short = [0; 32];
+        short[31] = 1;
+        let text = to_text(&owner, Some(short));
+        assert!(text.ends_with(".1"));
+        assert_eq!(from_text(&text)?.subaccount, Some(hex::encode(short)));
+
+        assert!(from_text(&text.replace(".1", ".01")).is_err());
+        assert!(from_text(&text.replace(".1", ".2")).is_err());
+        assert!(from_text(&format!("{owner_text}.1")).is_err());
+
+        Ok(())
+    }
+}
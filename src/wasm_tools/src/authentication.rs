@@ -0,0 +1,612 @@
+//! Authentication of ingress messages: request signatures, the public key
+//! schemes of the interface specification and sender delegations.
+//!
+//! Public keys are DER encoded `SubjectPublicKeyInfo`s, ECDSA signatures are
+//! the 64 bytes of `r` and `s`. Delegations are checked for expiry and targets,
+//! where the targets of every delegation in the chain must allow the canister.
+
+use ciborium::value::Value;
+use ed25519_dalek::Verifier;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    certificate::{self, Certificate},
+    hash_tree::{cbor_bytes, decode_cbor, domain_sep, HashTree},
+    request_id::hash_of,
+};
+
+pub const MAX_DELEGATIONS: usize = 20;
+
+const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const SECP256K1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x0a];
+const PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const COSE: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x83, 0xb8, 0x43, 0x01, 0x01];
+const CANISTER_SIGNATURE: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x83, 0xb8, 0x43, 0x01, 0x02];
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_BIT_STRING: u8 = 0x03;
+const DER_OID: u8 = 0x06;
+
+pub enum PublicKey {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Secp256k1(k256::ecdsa::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+    /// P-256 key of a WebAuthn authenticator, given as a COSE key
+    WebAuthn(p256::ecdsa::VerifyingKey),
+    Canister {
+        canister_id: Vec<u8>,
+        seed: Vec<u8>,
+    },
+}
+
+/// Splits the DER item at the start of `data` into its tag, its content and
+/// the bytes after it.
+fn der_item(data: &[u8]) -> Result<(u8, &[u8], &[u8]), String> {
+    let invalid = || "Invalid DER encoding".to_string();
+
+    let (&tag, rest) = data.split_first().ok_or_else(invalid)?;
+    let (&len, mut rest) = rest.split_first().ok_or_else(invalid)?;
+    let len = match len {
+        0..=0x7f => len as usize,
+        0x81..=0x82 => {
+            let n = (len & 0x7f) as usize;
+            let bytes = rest.get(..n).ok_or_else(invalid)?;
+            rest = &rest[n..];
+            bytes.iter().fold(0, |len, b| len << 8 | *b as usize)
+        }
+        _ => return Err(invalid()),
+    };
+    if rest.len() < len {
+        return Err(invalid());
+    }
+
+    Ok((tag, &rest[..len], &rest[len..]))
+}
+
+fn der_expect(data: &[u8], expected: u8) -> Result<(&[u8], &[u8]), String> {
+    match der_item(data)? {
+        (tag, content, rest) if tag == expected => Ok((content, rest)),
+        _ => Err("Unexpected DER item".to_string()),
+    }
+}
+
+/// Parts of a DER encoded `SubjectPublicKeyInfo`
+struct Spki<'a> {
+    algorithm: &'a [u8],
+    /// Parameter of the algorithm if it is an OID, the curve of ECDSA keys
+    curve: Option<&'a [u8]>,
+    key: &'a [u8],
+}
+
+fn spki(der: &[u8]) -> Result<Spki<'_>, String> {
+    let (info, _) = der_expect(der, DER_SEQUENCE)?;
+    let (algorithm, rest) = der_expect(info, DER_SEQUENCE)?;
+    let (bits, _) = der_expect(rest, DER_BIT_STRING)?;
+
+    let (algorithm, params) = der_expect(algorithm, DER_OID)?;
+    let curve = match der_item(params) {
+        Ok((DER_OID, curve, _)) => Some(curve),
+        _ => None,
+    };
+
+    match bits.split_first() {
+        Some((0, key)) => Ok(Spki {
+            algorithm,
+            curve,
+            key,
+        }),
+        _ => Err("Invalid public key bits".to_string()),
+    }
+}
+
+fn cose_key(data: &[u8]) -> Result<p256::ecdsa::VerifyingKey, String> {
+    let key = decode_cbor(data)?;
+    let fields = key.as_map().ok_or("COSE key is not a map")?;
+    let field = |label: i64| {
+        fields
+            .iter()
+            .find(|(k, _)| k.as_integer() == Some(label.into()))
+            .map(|(_, v)| v)
+            .ok_or_else(|| format!("COSE key has no label {label}"))
+    };
+
+    // EC2 key on P-256 for ECDSA with SHA-256
+    let int = |label| field(label).map(|v| v.as_integer().map(i128::from));
+    if int(1)? != Some(2) || int(3)? != Some(-7) || int(-1)? != Some(1) {
+        return Err("Only ECDSA P-256 COSE keys are supported".to_string());
+    }
+
+    let point = [
+        &[0x04][..],
+        &cbor_bytes(field(-2)?)?,
+        &cbor_bytes(field(-3)?)?,
+    ]
+    .concat();
+    p256::ecdsa::VerifyingKey::from_sec1_bytes(&point).map_err(|x| format!("{x}"))
+}
+
+impl PublicKey {
+    pub fn from_der(der: &[u8]) -> Result<PublicKey, String> {
+        let Spki {
+            algorithm,
+            curve,
+            key,
+        } = spki(der)?;
+        let invalid = |x: &dyn std::fmt::Display| format!("Invalid public key: {x}");
+
+        let key = match (algorithm, curve) {
+            (ED25519, _) => {
+                let key = key.try_into().map_err(|_| invalid(&"length"))?;
+                PublicKey::Ed25519(
+                    ed25519_dalek::VerifyingKey::from_bytes(key).map_err(|x| invalid(&x))?,
+                )
+            }
+            (EC_PUBLIC_KEY, Some(SECP256K1)) => PublicKey::Secp256k1(
+                k256::ecdsa::VerifyingKey::from_sec1_bytes(key).map_err(|x| invalid(&x))?,
+            ),
+            (EC_PUBLIC_KEY, Some(PRIME256V1)) => PublicKey::P256(
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(key).map_err(|x| invalid(&x))?,
+            ),
+            (COSE, _) => PublicKey::WebAuthn(cose_key(key)?),
+            (CANISTER_SIGNATURE, _) => {
+                let (&len, rest) = key.split_first().ok_or_else(|| invalid(&"length"))?;
+                if rest.len() < len as usize {
+                    return Err(invalid(&"length"));
+                }
+                let (canister_id, seed) = rest.split_at(len as usize);
+                PublicKey::Canister {
+                    canister_id: canister_id.to_vec(),
+                    seed: seed.to_vec(),
+                }
+            }
+            _ => return Err("Unsupported public key algorithm".to_string()),
+        };
+
+        Ok(key)
+    }
+
+    /// Checks `signature` of `msg`. Canister signatures are certified by the
+    /// subnet, `root_key` is the key their certificate is checked against.
+    pub fn verify(&self, msg: &[u8], signature: &[u8], root_key: &[u8]) -> Result<(), String> {
+        let invalid = |x: &dyn std::fmt::Display| format!("Invalid signature: {x}");
+
+        match self {
+            PublicKey::Ed25519(key) => {
+                let signature =
+                    ed25519_dalek::Signature::from_slice(signature).map_err(|x| invalid(&x))?;
+                key.verify(msg, &signature).map_err(|x| invalid(&x))
+            }
+            PublicKey::Secp256k1(key) => {
+                let signature =
+                    k256::ecdsa::Signature::from_slice(signature).map_err(|x| invalid(&x))?;
+                key.verify(msg, &signature).map_err(|x| invalid(&x))
+            }
+            PublicKey::P256(key) => {
+                let signature =
+                    p256::ecdsa::Signature::from_slice(signature).map_err(|x| invalid(&x))?;
+                key.verify(msg, &signature).map_err(|x| invalid(&x))
+            }
+            PublicKey::WebAuthn(key) => verify_webauthn(key, msg, signature),
+            PublicKey::Canister { canister_id, seed } => {
+                verify_canister_signature(canister_id, seed, msg, signature, root_key)
+            }
+        }
+    }
+}
+
+fn base64url(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            text.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    text
+}
+
+fn map_field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    value
+        .as_map()
+        .ok_or_else(|| format!("Expected a map with {name}"))?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(name))
+        .map(|(_, v)| v)
+        .ok_or_else(|| format!("Missing {name}"))
+}
+
+/// A WebAuthn signature signs the authenticator data followed by the hash of
+/// the client data, whose challenge is the message.
+fn verify_webauthn(
+    key: &p256::ecdsa::VerifyingKey,
+    msg: &[u8],
+    signature: &[u8],
+) -> Result<(), String> {
+    let signature = decode_cbor(signature)?;
+    let authenticator_data = cbor_bytes(map_field(&signature, "authenticator_data")?)?;
+    let client_data_json = cbor_bytes(map_field(&signature, "client_data_json")?)?;
+    let signature = cbor_bytes(map_field(&signature, "signature")?)?;
+
+    let client_data: serde_json::Value =
+        serde_json::from_slice(&client_data_json).map_err(|x| format!("{x}"))?;
+    if client_data["challenge"].as_str() != Some(&base64url(msg)) {
+        return Err("WebAuthn challenge does not match the message".to_string());
+    }
+
+    let signed = [&authenticator_data[..], &Sha256::digest(&client_data_json)].concat();
+    let signature = p256::ecdsa::Signature::from_der(&signature).map_err(|x| format!("{x}"))?;
+    key.verify(&signed, &signature)
+        .map_err(|x| format!("Invalid signature: {x}"))
+}
+
+/// A canister signature is a certificate of the certified data of the
+/// canister, which is the root of a tree holding `sig/<seed hash>/<msg hash>`.
+fn verify_canister_signature(
+    canister_id: &[u8],
+    seed: &[u8],
+    msg: &[u8],
+    signature: &[u8],
+    root_key: &[u8],
+) -> Result<(), String> {
+    let signature = decode_cbor(signature)?;
+    let cert = Certificate::decode(&cbor_bytes(map_field(&signature, "certificate")?)?)?;
+    let tree = HashTree::from_cbor(map_field(&signature, "tree")?)?;
+
+    certificate::verify_signed(&cert, root_key, canister_id)?;
+    let certified_data = cert
+        .tree
+        .lookup(&[b"canister", canister_id, b"certified_data"])
+        .ok_or("Certificate has no certified data of the canister")?;
+    if certified_data != tree.digest() {
+        return Err("Signature tree is not certified by the canister".to_string());
+    }
+
+    let seed_hash = Sha256::digest(seed);
+    let msg_hash = Sha256::digest(msg);
+    if tree.lookup(&[b"sig", &seed_hash, &msg_hash]).is_none() {
+        return Err("Signature tree does not sign the message".to_string());
+    }
+
+    Ok(())
+}
+
+/// Checks the `sender_delegation` chain, if any, and `sender_sig` of an
+/// envelope, which must come with a `sender_pubkey`. `canister_id` is the
+/// effective canister id the delegation targets have to allow.
+pub fn authenticate(
+    envelope: &Value,
+    request_id: &[u8; 32],
+    canister_id: &[u8],
+    root_key: &[u8],
+    now: u64,
+) -> Result<(), String> {
+    let mut key = cbor_bytes(map_field(envelope, "sender_pubkey")?)?;
+    let signature = cbor_bytes(map_field(envelope, "sender_sig")?)?;
+
+    let delegations = match map_field(envelope, "sender_delegation") {
+        Ok(delegations) => delegations
+            .as_array()
+            .ok_or("sender_delegation is not an array")?
+            .as_slice(),
+        Err(_) => &[],
+    };
+    if delegations.len() > MAX_DELEGATIONS {
+        return Err(format!("More than {MAX_DELEGATIONS} delegations"));
+    }
+
+    for signed in delegations {
+        let delegation = map_field(signed, "delegation")?;
+        let msg = [
+            &domain_sep("ic-request-auth-delegation")[..],
+            &hash_of(delegation)?,
+        ]
+        .concat();
+        let delegation_sig = cbor_bytes(map_field(signed, "signature")?)?;
+        PublicKey::from_der(&key)?.verify(&msg, &delegation_sig, root_key)?;
+
+        let expiration = map_field(delegation, "expiration")?
+            .as_integer()
+            .and_then(|x| u64::try_from(x).ok())
+            .ok_or("Delegation expiration is not a nat64")?;
+        if expiration < now {
+            return Err(format!("Delegation expired at {expiration}"));
+        }
+
+        if let Ok(targets) = map_field(delegation, "targets") {
+            let targets = targets
+                .as_array()
+                .ok_or("Delegation targets are not an array")?
+                .iter()
+                .map(cbor_bytes)
+                .collect::<Result<Vec<_>, _>>()?;
+            if !targets.iter().any(|t| t == canister_id) {
+                return Err("Canister is not a target of the delegation".to_string());
+            }
+        }
+
+        key = cbor_bytes(map_field(delegation, "pubkey")?)?;
+    }
+
+    let msg = [&domain_sep("ic-request")[..], request_id].concat();
+    PublicKey::from_der(&key)?.verify(&msg, &signature, root_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bls::{bls_init, bls_key_pair_from_seed},
+        certificate::build,
+        hash_tree::{encode_cbor, LabeledTree},
+    };
+    use ed25519_dalek::Signer;
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+    const CANISTER: [u8; 10] = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+    const REQUEST_ID: [u8; 32] = [0xab; 32];
+
+    fn der_len(len: usize) -> Vec<u8> {
+        match len {
+            0..=0x7f => vec![len as u8],
+            _ => vec![0x81, len as u8],
+        }
+    }
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        [&[tag][..], &der_len(content.len()), content].concat()
+    }
+
+    fn spki(algorithm: &[u8], curve: Option<&[u8]>, key: &[u8]) -> Vec<u8> {
+        let mut ids = der(DER_OID, algorithm);
+        if let Some(curve) = curve {
+            ids.extend(der(DER_OID, curve));
+        }
+        let bits = [&[0][..], key].concat();
+        der(
+            DER_SEQUENCE,
+            &[der(DER_SEQUENCE, &ids), der(DER_BIT_STRING, &bits)].concat(),
+        )
+    }
+
+    fn request_msg() -> Vec<u8> {
+        [&domain_sep("ic-request")[..], &REQUEST_ID].concat()
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.into())
+    }
+
+    fn envelope(pubkey: &[u8], sig: &[u8], delegations: Option<Vec<Value>>) -> Value {
+        let mut fields = vec![
+            (text("sender_pubkey"), Value::Bytes(pubkey.to_vec())),
+            (text("sender_sig"), Value::Bytes(sig.to_vec())),
+        ];
+        if let Some(delegations) = delegations {
+            fields.push((text("sender_delegation"), Value::Array(delegations)));
+        }
+        Value::Map(fields)
+    }
+
+    fn ed25519_key(seed: u8) -> (ed25519_dalek::SigningKey, Vec<u8>) {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+        let der = spki(ED25519, None, key.verifying_key().as_bytes());
+        (key, der)
+    }
+
+    fn p256_key(seed: u8) -> (p256::ecdsa::SigningKey, Vec<u8>) {
+        let key = p256::ecdsa::SigningKey::from_slice(&[seed; 32]).unwrap();
+        let point = key.verifying_key().to_encoded_point(false);
+        let der = spki(EC_PUBLIC_KEY, Some(PRIME256V1), point.as_bytes());
+        (key, der)
+    }
+
+    fn sign_delegation(
+        key: &ed25519_dalek::SigningKey,
+        pubkey: &[u8],
+        expiration: u64,
+        targets: Option<&[&[u8]]>,
+    ) -> Value {
+        let mut fields = vec![
+            (text("pubkey"), Value::Bytes(pubkey.to_vec())),
+            (text("expiration"), expiration.into()),
+        ];
+        if let Some(targets) = targets {
+            let targets = targets.iter().map(|t| Value::Bytes(t.to_vec())).collect();
+            fields.push((text("targets"), Value::Array(targets)));
+        }
+        let delegation = Value::Map(fields);
+
+        let msg = [
+            &domain_sep("ic-request-auth-delegation")[..],
+            &hash_of(&delegation).unwrap(),
+        ]
+        .concat();
+        let signature = key.sign(&msg).to_bytes().to_vec();
+        Value::Map(vec![
+            (text("delegation"), delegation),
+            (text("signature"), Value::Bytes(signature)),
+        ])
+    }
+
+    #[test]
+    fn request_signatures() -> Result<(), String> {
+        let (key, der) = ed25519_key(1);
+        let sig = key.sign(&request_msg()).to_bytes();
+        authenticate(
+            &envelope(&der, &sig, None),
+            &REQUEST_ID,
+            &CANISTER,
+            &[],
+            NOW,
+        )?;
+        let other_id = [0xcd; 32];
+        assert!(authenticate(&envelope(&der, &sig, None), &other_id, &CANISTER, &[], NOW).is_err());
+
+        let key = k256::ecdsa::SigningKey::from_slice(&[2; 32]).unwrap();
+        let point = key.verifying_key().to_encoded_point(false);
+        let der = spki(EC_PUBLIC_KEY, Some(SECP256K1), point.as_bytes());
+        let sig: k256::ecdsa::Signature =
+            k256::ecdsa::signature::Signer::sign(&key, &request_msg());
+        authenticate(
+            &envelope(&der, &sig.to_bytes(), None),
+            &REQUEST_ID,
+            &CANISTER,
+            &[],
+            NOW,
+        )?;
+
+        let (key, der) = p256_key(3);
+        let sig: p256::ecdsa::Signature =
+            p256::ecdsa::signature::Signer::sign(&key, &request_msg());
+        authenticate(
+            &envelope(&der, &sig.to_bytes(), None),
+            &REQUEST_ID,
+            &CANISTER,
+            &[],
+            NOW,
+        )?;
+
+        // The P-256 point read as a secp256k1 key
+        let der = spki(EC_PUBLIC_KEY, Some(SECP256K1), &der[der.len() - 65..]);
+        let request = envelope(&der, &sig.to_bytes(), None);
+        assert!(authenticate(&request, &REQUEST_ID, &CANISTER, &[], NOW).is_err());
+
+        assert!(PublicKey::from_der(&spki(&[0x2a], None, &[1])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn delegations() -> Result<(), String> {
+        let (root, root_der) = ed25519_key(4);
+        let (session, session_der) = p256_key(5);
+        let sig: p256::ecdsa::Signature =
+            p256::ecdsa::signature::Signer::sign(&session, &request_msg());
+        let sig = sig.to_bytes();
+
+        let delegation = sign_delegation(&root, &session_der, NOW + 1, Some(&[&CANISTER]));
+        let request = envelope(&root_der, &sig, Some(vec![delegation]));
+        authenticate(&request, &REQUEST_ID, &CANISTER, &[], NOW)?;
+
+        let other = [0, 0, 0, 0, 0, 0, 0, 2, 1, 1];
+        assert!(authenticate(&request, &REQUEST_ID, &other, &[], NOW).is_err());
+
+        let expired = sign_delegation(&root, &session_der, NOW - 1, None);
+        let request = envelope(&root_der, &sig, Some(vec![expired]));
+        assert!(authenticate(&request, &REQUEST_ID, &CANISTER, &[], NOW).is_err());
+
+        // Signed by a key that is not the sender
+        let (other_key, _) = ed25519_key(6);
+        let forged = sign_delegation(&other_key, &session_der, NOW + 1, None);
+        let request = envelope(&root_der, &sig, Some(vec![forged]));
+        assert!(authenticate(&request, &REQUEST_ID, &CANISTER, &[], NOW).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn webauthn() -> Result<(), String> {
+        let (key, _) = p256_key(7);
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose = encode_cbor(Value::Map(vec![
+            (1.into(), 2.into()),
+            (3.into(), (-7).into()),
+            ((-1).into(), 1.into()),
+            ((-2).into(), Value::Bytes(point.x().unwrap().to_vec())),
+            ((-3).into(), Value::Bytes(point.y().unwrap().to_vec())),
+        ]))?;
+        let der = spki(COSE, None, &cose);
+
+        let sign = |challenge: &[u8]| -> Result<Vec<u8>, String> {
+            let authenticator_data = vec![0x11; 37];
+            let client_data_json = format!(
+                r#"{{"type":"webauthn.get","challenge":"{}","origin":"http://localhost"}}"#,
+                base64url(challenge)
+            );
+            let signed = [
+                &authenticator_data[..],
+                &Sha256::digest(client_data_json.as_bytes()),
+            ]
+            .concat();
+            let sig: p256::ecdsa::Signature = p256::ecdsa::signature::Signer::sign(&key, &signed);
+            encode_cbor(Value::Map(vec![
+                (text("authenticator_data"), Value::Bytes(authenticator_data)),
+                (text("client_data_json"), text(&client_data_json)),
+                (
+                    text("signature"),
+                    Value::Bytes(sig.to_der().as_bytes().to_vec()),
+                ),
+            ]))
+        };
+
+        let sig = sign(&request_msg())?;
+        authenticate(
+            &envelope(&der, &sig, None),
+            &REQUEST_ID,
+            &CANISTER,
+            &[],
+            NOW,
+        )?;
+
+        let sig = sign(b"something else")?;
+        assert!(authenticate(
+            &envelope(&der, &sig, None),
+            &REQUEST_ID,
+            &CANISTER,
+            &[],
+            NOW
+        )
+        .is_err());
+
+        assert_eq!(base64url(b"\xfb\xff"), "-_8");
+
+        Ok(())
+    }
+
+    #[test]
+    fn canister_signature() -> Result<(), String> {
+        bls_init()?;
+        let root = bls_key_pair_from_seed(&[8u8; 32])?;
+        let seed = b"user seed";
+
+        let mut sigs = LabeledTree::default();
+        let path = [
+            b"sig".to_vec(),
+            Sha256::digest(seed).to_vec(),
+            Sha256::digest(request_msg()).to_vec(),
+        ];
+        sigs.insert(&path, Vec::new())?;
+        let sigs = sigs.to_hash_tree();
+
+        let mut state = LabeledTree::default();
+        let path = [
+            b"canister".to_vec(),
+            CANISTER.to_vec(),
+            b"certified_data".to_vec(),
+        ];
+        state.insert(&path, sigs.digest().to_vec())?;
+        let cert = build(&state.to_hash_tree(), &root[..48], None)?;
+
+        let sig = encode_cbor(Value::Map(vec![
+            (text("certificate"), Value::Bytes(cert)),
+            (text("tree"), sigs.to_cbor()),
+        ]))?;
+        let key = [&[CANISTER.len() as u8][..], &CANISTER, seed].concat();
+        let der = spki(CANISTER_SIGNATURE, None, &key);
+
+        let request = envelope(&der, &sig, None);
+        authenticate(&request, &REQUEST_ID, &CANISTER, &root[48..], NOW)?;
+        assert!(authenticate(&request, &[0xcd; 32], &CANISTER, &root[48..], NOW).is_err());
+
+        let other = bls_key_pair_from_seed(&[9u8; 32])?;
+        assert!(authenticate(&request, &REQUEST_ID, &CANISTER, &other[48..], NOW).is_err());
+
+        Ok(())
+    }
+}
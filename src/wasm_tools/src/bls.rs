@@ -2,24 +2,66 @@ use wasm_bindgen::prelude::*;
 
 use miracl_core_bls12381::*;
 
-#[wasm_bindgen]
-pub fn bls_get_key_pair() -> Result<Vec<u8>, String> {
-    const BFS: usize = bls12381::bls::BFS;
-    const BGS: usize = bls12381::bls::BGS;
+const BFS: usize = bls12381::bls::BFS;
+const BGS: usize = bls12381::bls::BGS;
+
+const G1S: usize = BFS; /* Group 1 Size  - compressed */
+const G2S: usize = 2 * BFS; /* Group 2 Size  - compressed */
+
+/// Length of a secret key, the first part of a key pair
+pub const SECRET_KEY_SIZE: usize = BGS;
 
-    const G1S: usize = BFS; /* Group 1 Size  - compressed */
-    const G2S: usize = 2 * BFS; /* Group 2 Size  - compressed */
+/// Input key material has to be at least 32 bytes, and miracl takes at most 64
+const MIN_SEED: usize = 32;
+const MAX_SEED: usize = 64;
+
+#[wasm_bindgen]
+pub fn bls_init() -> Result<(), String> {
+    match bls12381::bls::init() {
+        bls12381::bls::BLS_OK => Ok(()),
+        _ => Err("Cannot initialize BLS".to_string()),
+    }
+}
 
+/// Secret key followed by the public key, derived from `ikm`.
+fn key_pair(ikm: &[u8]) -> Vec<u8> {
     let mut r: [u8; BGS + G2S] = [0; BGS + G2S];
 
     let (s, w) = r[..].split_at_mut(BGS);
+    bls12381::bls::key_pair_generate(ikm, s, w);
+
+    r.to_vec()
+}
+
+#[wasm_bindgen]
+pub fn bls_get_key_pair() -> Result<Vec<u8>, String> {
     let mut ikm: [u8; 32] = [0; 32];
 
     getrandom::getrandom(&mut ikm).map_err(|e| format!("{e}"))?;
 
-    bls12381::bls::key_pair_generate(&ikm, s, w);
+    Ok(key_pair(&ikm))
+}
+
+/// Same key pair for the same seed, so a root key can be pinned across runs.
+#[wasm_bindgen]
+pub fn bls_key_pair_from_seed(seed: &[u8]) -> Result<Vec<u8>, String> {
+    if !(MIN_SEED..=MAX_SEED).contains(&seed.len()) {
+        return Err(format!(
+            "Seed has to be {MIN_SEED} to {MAX_SEED} bytes long, got {}",
+            seed.len()
+        ));
+    }
+
+    Ok(key_pair(seed))
+}
+
+#[wasm_bindgen]
+pub fn bls_verify(sig: &[u8], m: &[u8], w: &[u8]) -> bool {
+    if sig.len() != G1S || w.len() != G2S {
+        return false;
+    }
 
-    Ok(r.to_vec())
+    bls12381::bls::core_verify(sig, m, w) == bls12381::bls::BLS_OK
 }
 
 #[wasm_bindgen]
@@ -65,4 +107,26 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn keys_from_seed() -> Result<(), String> {
+        bls_init()?;
+
+        let seed = [7u8; 32];
+        let keys = bls_key_pair_from_seed(&seed)?;
+        assert_eq!(keys, bls_key_pair_from_seed(&seed)?);
+        assert_ne!(keys, bls_key_pair_from_seed(&[8u8; 32])?);
+
+        let (private, public) = keys.split_at(48);
+        let sig = bls_sign(b"hello".as_ref(), private);
+
+        assert!(super::bls_verify(&sig, b"hello".as_ref(), public));
+        assert!(!super::bls_verify(&sig, b"hallo".as_ref(), public));
+        assert!(!super::bls_verify(&sig[1..], b"hello".as_ref(), public));
+
+        assert!(bls_key_pair_from_seed(&[0u8; 31]).is_err());
+        assert!(bls_key_pair_from_seed(&[0u8; 65]).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,425 @@
+//! Certificates of the IC interface specification.
+
+use ciborium::value::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    bls::{bls_init, bls_sign, bls_verify, SECRET_KEY_SIZE},
+    hash_tree::{cbor_bytes, decode_cbor, domain_sep, encode_cbor, HashTree, LabeledTree},
+};
+
+/// DER prefix of BLS12-381 public keys, `DER_PREFIX` in `bls.ts`
+pub const DER_PREFIX: [u8; 37] = [
+    0x30, 0x81, 0x82, 0x30, 0x1d, 0x06, 0x0d, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xdc, 0x7c, 0x05,
+    0x03, 0x01, 0x02, 0x01, 0x06, 0x0c, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xdc, 0x7c, 0x05, 0x03,
+    0x02, 0x01, 0x03, 0x61, 0x00,
+];
+
+/// How far the `time` of a certificate may be from the current time, the
+/// ingress expiry agents allow.
+pub const MAX_TIME_OFFSET_NS: u64 = 5 * 60 * 1_000_000_000;
+
+/// Message the subnet signs to certify a tree with root hash `root_hash`.
+pub fn state_root_message(root_hash: &[u8; 32]) -> Vec<u8> {
+    let mut msg = domain_sep("ic-state-root");
+    msg.extend_from_slice(root_hash);
+    msg
+}
+
+/// CBOR of the certificate `{ tree, signature, delegation? }`, `delegation`
+/// is the `{ subnet_id, certificate }` map of a subnet delegation.
+pub fn encode(
+    tree: &HashTree,
+    signature: &[u8],
+    delegation: Option<Value>,
+) -> Result<Vec<u8>, String> {
+    let mut fields = vec![
+        (Value::Text("tree".into()), tree.to_cbor()),
+        (
+            Value::Text("signature".into()),
+            Value::Bytes(signature.to_vec()),
+        ),
+    ];
+    if let Some(delegation) = delegation {
+        fields.push((Value::Text("delegation".into()), delegation));
+    }
+
+    encode_cbor(Value::Map(fields))
+}
+
+/// Signs `tree` with the BLS secret key and encodes the certificate.
+pub fn build(
+    tree: &HashTree,
+    secret_key: &[u8],
+    delegation: Option<Value>,
+) -> Result<Vec<u8>, String> {
+    if secret_key.len() != SECRET_KEY_SIZE {
+        return Err(format!(
+            "Secret key has to be {SECRET_KEY_SIZE} bytes long, got {}",
+            secret_key.len()
+        ));
+    }
+    let signature = bls_sign(&state_root_message(&tree.digest()), secret_key);
+
+    encode(tree, &signature, delegation)
+}
+
+/// Builds a certificate for the CBOR array of `[path, value]` pairs in
+/// `pairs`. `delegation` is the CBOR of a subnet delegation, if any.
+#[wasm_bindgen]
+pub fn certificate_build(
+    pairs: &[u8],
+    secret_key: &[u8],
+    delegation: Option<Vec<u8>>,
+) -> Result<Vec<u8>, String> {
+    let tree = LabeledTree::from_cbor(&decode_cbor(pairs)?)?.to_hash_tree();
+    let delegation = delegation.map(|x| decode_cbor(&x)).transpose()?;
+
+    build(&tree, secret_key, delegation)
+}
+
+/// Decoded certificate, `delegation` holds the subnet id and the certificate
+/// of the delegation.
+pub struct Certificate {
+    pub tree: HashTree,
+    pub signature: Vec<u8>,
+    pub delegation: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Certificate {
+    pub fn decode(data: &[u8]) -> Result<Certificate, String> {
+        let value = decode_cbor(data)?;
+        let fields = value.as_map().ok_or("Certificate is not a map")?;
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k.as_text() == Some(name))
+                .map(|(_, v)| v)
+        };
+
+        let tree = HashTree::from_cbor(field("tree").ok_or("Certificate has no tree")?)?;
+        let signature = cbor_bytes(field("signature").ok_or("Certificate has no signature")?)?;
+        let delegation = match field("delegation") {
+            Some(delegation) => {
+                let fields = delegation.as_map().ok_or("Delegation is not a map")?;
+                let field = |name: &str| {
+                    fields
+                        .iter()
+                        .find(|(k, _)| k.as_text() == Some(name))
+                        .map(|(_, v)| cbor_bytes(v))
+                        .ok_or_else(|| format!("Delegation has no {name}"))
+                };
+                Some((field("subnet_id")??, field("certificate")??))
+            }
+            None => None,
+        };
+
+        Ok(Certificate {
+            tree,
+            signature,
+            delegation,
+        })
+    }
+}
+
+/// Raw BLS public key, from either its DER or its raw form.
+pub fn public_key(key: &[u8]) -> Result<&[u8], String> {
+    match key.strip_prefix(&DER_PREFIX[..]) {
+        Some(key) => Ok(key),
+        None if key.len() == 96 => Ok(key),
+        None => Err("Invalid BLS public key".to_string()),
+    }
+}
+
+pub fn decode_leb128(data: &[u8]) -> Result<u64, String> {
+    let mut value: u64 = 0;
+    for (i, byte) in data.iter().enumerate() {
+        if i >= 10 {
+            break;
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Invalid LEB128 number".to_string())
+}
+
+/// Whether `canister_id` is in one of the `[start, end]` ranges of the CBOR
+/// `canister_ranges` of a subnet.
+fn in_ranges(canister_id: &[u8], ranges: &[u8]) -> Result<bool, String> {
+    let ranges = decode_cbor(ranges)?;
+    for range in ranges.as_array().ok_or("Invalid canister ranges")? {
+        let Some([start, end]) = range.as_array().map(|x| x.as_slice()) else {
+            return Err("Invalid canister range".to_string());
+        };
+        if cbor_bytes(start)?.as_slice() <= canister_id
+            && canister_id <= cbor_bytes(end)?.as_slice()
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Key the certificate is signed with: `root_key`, or the key of the subnet
+/// its delegation names when the delegation is valid for `canister_id`.
+fn signing_key(cert: &Certificate, root_key: &[u8], canister_id: &[u8]) -> Result<Vec<u8>, String> {
+    let Some((subnet_id, delegation)) = &cert.delegation else {
+        return Ok(public_key(root_key)?.to_vec());
+    };
+
+    let delegation = Certificate::decode(delegation)?;
+    if delegation.delegation.is_some() {
+        return Err("Delegation certificate has a delegation".to_string());
+    }
+    verify_signature(&delegation, public_key(root_key)?)?;
+
+    let ranges = delegation
+        .tree
+        .lookup(&[b"subnet", subnet_id, b"canister_ranges"])
+        .ok_or("Delegation has no canister ranges")?;
+    if !in_ranges(canister_id, ranges)? {
+        return Err("Canister is not in the ranges of the delegated subnet".to_string());
+    }
+
+    let key = delegation
+        .tree
+        .lookup(&[b"subnet", subnet_id, b"public_key"])
+        .ok_or("Delegation has no public key")?;
+    Ok(public_key(key)?.to_vec())
+}
+
+fn verify_signature(cert: &Certificate, key: &[u8]) -> Result<(), String> {
+    let msg = state_root_message(&cert.tree.digest());
+    if !bls_verify(&cert.signature, &msg, key) {
+        return Err("Invalid certificate signature".to_string());
+    }
+    Ok(())
+}
+
+/// Checks the signature and delegation of a certificate, but not its time.
+pub fn verify_signed(
+    cert: &Certificate,
+    root_key: &[u8],
+    canister_id: &[u8],
+) -> Result<(), String> {
+    let key = signing_key(cert, root_key, canister_id)?;
+    verify_signature(cert, &key)
+}
+
+/// Checks the signature, delegation and time of a certificate. `now` is in
+/// nanoseconds since the epoch.
+pub fn verify(
+    cert: &Certificate,
+    root_key: &[u8],
+    canister_id: &[u8],
+    now: u64,
+) -> Result<(), String> {
+    verify_signed(cert, root_key, canister_id)?;
+
+    let time = cert
+        .tree
+        .lookup(&[b"time"])
+        .ok_or("Certificate has no time")?;
+    let time = decode_leb128(time)?;
+    if time.abs_diff(now) > MAX_TIME_OFFSET_NS {
+        return Err(format!(
+            "Certificate time {time} is more than {MAX_TIME_OFFSET_NS}ns away from {now}"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Verifies a certificate against the root key, DER or raw, and returns its
+/// tree as CBOR.
+#[wasm_bindgen]
+pub fn verify_certificate(
+    cert: &[u8],
+    root_key: &[u8],
+    effective_canister_id: &[u8],
+    now: u64,
+) -> Result<Vec<u8>, String> {
+    bls_init()?;
+
+    let cert = Certificate::decode(cert)?;
+    verify(&cert, root_key, effective_canister_id, now)?;
+
+    encode_cbor(cert.tree.to_cbor())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bls::bls_key_pair_from_seed;
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+
+    fn leb128(mut n: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    fn certificate(
+        pairs: &[(&[&[u8]], &[u8])],
+        secret: &[u8],
+        delegation: Option<Value>,
+    ) -> Result<Vec<u8>, String> {
+        let mut tree = LabeledTree::default();
+        for (path, value) in pairs {
+            let path: Vec<_> = path.iter().map(|l| l.to_vec()).collect();
+            tree.insert(&path, value.to_vec())?;
+        }
+        build(&tree.to_hash_tree(), secret, delegation)
+    }
+
+    #[test]
+    fn build_certificate() -> Result<(), String> {
+        bls_init()?;
+        let keys = bls_key_pair_from_seed(&[1u8; 32])?;
+        let (secret, public) = keys.split_at(48);
+
+        let pairs = Value::Array(vec![
+            Value::Array(vec![
+                Value::Array(vec!["time".into()]),
+                Value::Bytes(vec![1]),
+            ]),
+            Value::Array(vec![
+                Value::Array(vec![
+                    "request_status".into(),
+                    Value::Bytes(vec![0xab; 32]),
+                    "status".into(),
+                ]),
+                "replied".into(),
+            ]),
+        ]);
+        let cert = certificate_build(&encode_cbor(pairs)?, secret, None)?;
+
+        assert_eq!(&cert[..3], &[0xd9, 0xd9, 0xf7]);
+        let Value::Map(fields) = decode_cbor(&cert)? else {
+            return Err("certificate is not a map".to_string());
+        };
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].0, Value::Text("tree".into()));
+
+        let mut tree = LabeledTree::default();
+        tree.insert(&[b"time".to_vec()], vec![1])?;
+        let path = [
+            b"request_status".to_vec(),
+            vec![0xab; 32],
+            b"status".to_vec(),
+        ];
+        tree.insert(&path, b"replied".to_vec())?;
+        let tree = tree.to_hash_tree();
+        assert_eq!(fields[0].1, tree.to_cbor());
+
+        let Value::Bytes(signature) = &fields[1].1 else {
+            return Err("signature is not a blob".to_string());
+        };
+        let msg = state_root_message(&tree.digest());
+        assert!(bls_verify(signature, &msg, public));
+
+        let delegation = Value::Map(vec![("subnet_id".into(), Value::Bytes(vec![1, 2, 3]))]);
+        let pairs = Value::Array(vec![]);
+        let cert = certificate_build(
+            &encode_cbor(pairs)?,
+            secret,
+            Some(encode_cbor(delegation.clone())?),
+        )?;
+        let Value::Map(fields) = decode_cbor(&cert)? else {
+            return Err("certificate is not a map".to_string());
+        };
+        assert_eq!(fields[0].1, HashTree::Empty.to_cbor());
+        assert_eq!(fields[2], ("delegation".into(), delegation));
+
+        let pairs = encode_cbor(Value::Array(vec![]))?;
+        assert!(certificate_build(&pairs, &secret[..47], None).is_err());
+        assert!(certificate_build(&pairs, &keys, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_root_certificate() -> Result<(), String> {
+        bls_init()?;
+        let keys = bls_key_pair_from_seed(&[2u8; 32])?;
+        let (secret, public) = keys.split_at(48);
+        let der = [&DER_PREFIX[..], public].concat();
+        let canister = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+
+        let cert = certificate(&[(&[b"time"], &leb128(NOW))], secret, None)?;
+        let tree = verify_certificate(&cert, &der, &canister, NOW + 1_000)?;
+        assert_eq!(
+            HashTree::from_cbor(&decode_cbor(&tree)?)?.lookup(&[b"time"]),
+            Some(leb128(NOW).as_slice())
+        );
+        verify_certificate(&cert, public, &canister, NOW)?;
+
+        let late = NOW + MAX_TIME_OFFSET_NS + 1;
+        assert!(verify_certificate(&cert, &der, &canister, late).is_err());
+
+        let other = bls_key_pair_from_seed(&[3u8; 32])?;
+        assert!(verify_certificate(&cert, &other[48..], &canister, NOW).is_err());
+
+        let cert = certificate(&[(&[b"other"], b"")], secret, None)?;
+        assert!(verify_certificate(&cert, &der, &canister, NOW).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_delegation() -> Result<(), String> {
+        bls_init()?;
+        let root = bls_key_pair_from_seed(&[4u8; 32])?;
+        let subnet = bls_key_pair_from_seed(&[5u8; 32])?;
+        let subnet_der = [&DER_PREFIX[..], &subnet[48..]].concat();
+        let subnet_id = [7u8; 29];
+
+        let start = [0, 0, 0, 0, 0, 0x10, 0, 0, 1, 1];
+        let end = [0, 0, 0, 0, 0, 0x1f, 0xff, 0xff, 1, 1];
+        let ranges = Value::Array(vec![Value::Array(vec![
+            Value::Bytes(start.to_vec()),
+            Value::Bytes(end.to_vec()),
+        ])]);
+        let ranges = encode_cbor(ranges)?;
+
+        let delegation_cert = certificate(
+            &[
+                (&[b"subnet", &subnet_id, b"canister_ranges"], &ranges),
+                (&[b"subnet", &subnet_id, b"public_key"], &subnet_der),
+                (&[b"time"], &leb128(NOW)),
+            ],
+            &root[..48],
+            None,
+        )?;
+        let delegation = Value::Map(vec![
+            ("subnet_id".into(), Value::Bytes(subnet_id.to_vec())),
+            ("certificate".into(), Value::Bytes(delegation_cert)),
+        ]);
+        let cert = certificate(
+            &[(&[b"time"], &leb128(NOW))],
+            &subnet[..48],
+            Some(delegation),
+        )?;
+
+        let inside = [0, 0, 0, 0, 0, 0x10, 0, 5, 1, 1];
+        verify_certificate(&cert, &root[48..], &inside, NOW)?;
+
+        let outside = [0, 0, 0, 0, 0, 0x20, 0, 0, 1, 1];
+        assert!(verify_certificate(&cert, &root[48..], &outside, NOW).is_err());
+
+        // The subnet key is not trusted as root
+        assert!(verify_certificate(&cert, &subnet[48..], &inside, NOW).is_err());
+
+        Ok(())
+    }
+}
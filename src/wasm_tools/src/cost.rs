@@ -0,0 +1,108 @@
+//! Size and cycles cost of messages under the fee schedule of the IC.
+//!
+//! Fees are the ones of a 13 node application subnet and scale linearly with
+//! the number of nodes, as `CyclesAccountManager::scale_cost` does. The size of
+//! a message is its method name plus its argument.
+
+use serde::{Deserialize, Serialize};
+
+const REFERENCE_SUBNET_SIZE: u64 = 13;
+
+const INGRESS_MESSAGE_RECEPTION_FEE: u64 = 1_200_000;
+const INGRESS_BYTE_RECEPTION_FEE: u64 = 2_000;
+const XNET_CALL_FEE: u64 = 260_000;
+const XNET_BYTE_TRANSMISSION_FEE: u64 = 1_000;
+
+pub const MAX_INGRESS_BYTES_PER_MESSAGE: u64 = 2 * 1024 * 1024;
+pub const MAX_INTER_CANISTER_PAYLOAD_IN_BYTES: u64 = 2 * 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Number of nodes of the subnet the receiving canister runs on
+    pub subnet_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            subnet_size: REFERENCE_SUBNET_SIZE,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Cost {
+    /// Fee charged for every message
+    pub base_fee: u64,
+    /// Fee charged for every byte of the message
+    pub per_byte_fee: u64,
+    /// `per_byte_fee` times the size of the message
+    pub transmission_fee: u64,
+    pub total: u64,
+    pub limit: u64,
+    pub within_limit: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+    pub size: u64,
+    pub subnet_size: u64,
+    /// Paid by the canister when it accepts an ingress message
+    pub ingress: Cost,
+    /// Paid by the caller of an inter-canister call
+    pub inter_canister: Cost,
+}
+
+/// `fee * units` scaled to `subnet_size` nodes, computed in `u128` so only a
+/// result that does not fit cycles of a `u64` is an error.
+fn scale(fee: u64, units: u64, subnet_size: u64) -> Result<u64, String> {
+    let cycles = fee as u128 * units as u128 * subnet_size as u128 / REFERENCE_SUBNET_SIZE as u128;
+    u64::try_from(cycles).map_err(|_| format!("Cost of {cycles} cycles does not fit into u64"))
+}
+
+fn cost(
+    size: u64,
+    base_fee: u64,
+    per_byte_fee: u64,
+    limit: u64,
+    config: &Config,
+) -> Result<Cost, String> {
+    let base_fee = scale(base_fee, 1, config.subnet_size)?;
+    let transmission_fee = scale(per_byte_fee, size, config.subnet_size)?;
+    let total = base_fee
+        .checked_add(transmission_fee)
+        .ok_or_else(|| "Total cost does not fit into u64".to_string())?;
+
+    Ok(Cost {
+        base_fee,
+        per_byte_fee: scale(per_byte_fee, 1, config.subnet_size)?,
+        transmission_fee,
+        total,
+        limit,
+        within_limit: size <= limit,
+    })
+}
+
+pub fn estimate(method: &str, arg: &[u8], config: &Config) -> Result<Report, String> {
+    let size = (method.len() + arg.len()) as u64;
+
+    Ok(Report {
+        size,
+        subnet_size: config.subnet_size,
+        ingress: cost(
+            size,
+            INGRESS_MESSAGE_RECEPTION_FEE,
+            INGRESS_BYTE_RECEPTION_FEE,
+            MAX_INGRESS_BYTES_PER_MESSAGE,
+            config,
+        )?,
+        inter_canister: cost(
+            size,
+            XNET_CALL_FEE,
+            XNET_BYTE_TRANSMISSION_FEE,
+            MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+            config,
+        )?,
+    })
+}
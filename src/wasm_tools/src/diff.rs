@@ -0,0 +1,355 @@
+//! Structured changelog between two versions of a Candid interface.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{
+    types::{Field, Function, Type},
+    TypeEnv,
+};
+use serde::Serialize;
+
+use crate::modes::{Composite, Mode};
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    MethodAdded {
+        method: String,
+    },
+    MethodRemoved {
+        method: String,
+    },
+    ModeChanged {
+        method: String,
+        old: String,
+        new: String,
+    },
+    TypeAdded {
+        name: String,
+    },
+    TypeRemoved {
+        name: String,
+    },
+    TypeRenamed {
+        old: String,
+        new: String,
+    },
+    FieldAdded {
+        path: String,
+        field: String,
+    },
+    FieldRemoved {
+        path: String,
+        field: String,
+    },
+    /// `optional` tells whether the value became optional or required
+    OptionalChanged {
+        path: String,
+        optional: bool,
+    },
+    TypeChanged {
+        path: String,
+        old: String,
+        new: String,
+    },
+    /// `breaking` tells whether one of the new init args is required
+    InitAdded {
+        args: usize,
+        breaking: bool,
+    },
+    InitRemoved {
+        args: usize,
+    },
+}
+
+struct Differ<'a> {
+    old: &'a TypeEnv,
+    new: &'a TypeEnv,
+    old_composite: &'a Composite,
+    new_composite: &'a Composite,
+    /// Old type names mapped to their new names
+    renames: BTreeMap<String, String>,
+    /// Named type pairs already compared, stops recursive types
+    seen: BTreeSet<(String, String)>,
+    changes: Vec<Change>,
+}
+
+fn resolve<'a>(env: &'a TypeEnv, ty: &'a Type) -> &'a Type {
+    match ty {
+        Type::Var(id) => env.find_type(id).unwrap_or(ty),
+        _ => ty,
+    }
+}
+
+fn fields_by_label(fs: &[Field]) -> BTreeMap<String, &Type> {
+    fs.iter().map(|f| (f.id.to_string(), &f.ty)).collect()
+}
+
+impl<'a> Differ<'a> {
+    fn same_name(&self, old: &str, new: &str) -> bool {
+        self.renames.get(old).map(|x| x.as_str()).unwrap_or(old) == new
+    }
+
+    fn compare(&mut self, path: &str, old: &Type, new: &Type) {
+        match (old, new) {
+            // Named types are compared on their own, an alias that changed
+            // into another type is unfolded once
+            (Type::Var(a), Type::Var(b)) if self.same_name(a, b) => {}
+            (Type::Var(_), _) | (_, Type::Var(_)) => {
+                if self.seen.insert((format!("{old}"), format!("{new}"))) {
+                    let old = resolve(self.old, old);
+                    let new = resolve(self.new, new);
+                    self.compare(path, old, new);
+                }
+            }
+            (Type::Opt(a), Type::Opt(b)) => self.compare(path, a, b),
+            (Type::Opt(a), b) => {
+                self.changes.push(Change::OptionalChanged {
+                    path: path.to_string(),
+                    optional: false,
+                });
+                self.compare(path, a, b);
+            }
+            (a, Type::Opt(b)) => {
+                self.changes.push(Change::OptionalChanged {
+                    path: path.to_string(),
+                    optional: true,
+                });
+                self.compare(path, a, b);
+            }
+            (Type::Vec(a), Type::Vec(b)) => self.compare(&format!("{path}[]"), a, b),
+            (Type::Record(a), Type::Record(b)) | (Type::Variant(a), Type::Variant(b)) => {
+                self.compare_fields(path, a, b)
+            }
+            (Type::Func(a), Type::Func(b)) => {
+                self.compare_func(path, a, b, (Mode::of(a), Mode::of(b)))
+            }
+            (Type::Service(a), Type::Service(b)) => {
+                let composite = (
+                    self.old_composite.of_type(path),
+                    self.new_composite.of_type(path),
+                );
+                self.compare_service(path, a, b, composite)
+            }
+            (a, b) if a == b => {}
+            (a, b) => self.changes.push(Change::TypeChanged {
+                path: path.to_string(),
+                old: format!("{a}"),
+                new: format!("{b}"),
+            }),
+        }
+    }
+
+    fn compare_fields(&mut self, path: &str, old: &[Field], new: &[Field]) {
+        let old = fields_by_label(old);
+        let new = fields_by_label(new);
+
+        for (label, ty) in old.iter() {
+            match new.get(label) {
+                Some(new_ty) => self.compare(&format!("{path}.{label}"), ty, new_ty),
+                None => self.changes.push(Change::FieldRemoved {
+                    path: path.to_string(),
+                    field: label.clone(),
+                }),
+            }
+        }
+
+        for label in new.keys().filter(|x| !old.contains_key(*x)) {
+            self.changes.push(Change::FieldAdded {
+                path: path.to_string(),
+                field: label.clone(),
+            });
+        }
+    }
+
+    fn compare_list(&mut self, path: &str, old: &[Type], new: &[Type]) {
+        for (i, (a, b)) in old.iter().zip(new.iter()).enumerate() {
+            self.compare(&format!("{path}.{i}"), a, b);
+        }
+        for i in new.len()..old.len() {
+            self.changes.push(Change::FieldRemoved {
+                path: path.to_string(),
+                field: i.to_string(),
+            });
+        }
+        for i in old.len()..new.len() {
+            self.changes.push(Change::FieldAdded {
+                path: path.to_string(),
+                field: i.to_string(),
+            });
+        }
+    }
+
+    fn compare_func(&mut self, path: &str, old: &Function, new: &Function, modes: (Mode, Mode)) {
+        let (old_mode, new_mode) = modes;
+        if old_mode != new_mode {
+            self.changes.push(Change::ModeChanged {
+                method: path.to_string(),
+                old: old_mode.name().to_string(),
+                new: new_mode.name().to_string(),
+            });
+        }
+
+        self.compare_list(&format!("{path}.args"), &old.args, &new.args);
+        self.compare_list(&format!("{path}.rets"), &old.rets, &new.rets);
+    }
+
+    /// `composite` holds the composite queries of the old and new service.
+    fn compare_service(
+        &mut self,
+        path: &str,
+        old: &[(String, Type)],
+        new: &[(String, Type)],
+        composite: (&BTreeSet<String>, &BTreeSet<String>),
+    ) {
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{path}.")
+        };
+        let new_methods: BTreeMap<_, _> = new.iter().map(|(k, v)| (k, v)).collect();
+        let old_methods: BTreeMap<_, _> = old.iter().map(|(k, v)| (k, v)).collect();
+
+        for (name, ty) in old_methods.iter() {
+            match new_methods.get(name) {
+                Some(new_ty) => {
+                    let path = format!("{prefix}{name}");
+                    match (ty, new_ty) {
+                        (Type::Func(a), Type::Func(b)) => {
+                            let modes = (
+                                Mode::of_method(name, a, composite.0),
+                                Mode::of_method(name, b, composite.1),
+                            );
+                            self.compare_func(&path, a, b, modes)
+                        }
+                        _ => self.compare(&path, ty, new_ty),
+                    }
+                }
+                None => self.changes.push(Change::MethodRemoved {
+                    method: format!("{prefix}{name}"),
+                }),
+            }
+        }
+
+        for name in new_methods.keys().filter(|x| !old_methods.contains_key(*x)) {
+            self.changes.push(Change::MethodAdded {
+                method: format!("{prefix}{name}"),
+            });
+        }
+    }
+}
+
+/// Init args of an actor, `None` when it takes none.
+fn init_args(actor: &Type) -> Option<&[Type]> {
+    match actor {
+        Type::Class(args, _) if !args.is_empty() => Some(args),
+        _ => None,
+    }
+}
+
+/// Whether an arg can be left out by callers that do not know about it.
+fn is_optional(env: &TypeEnv, ty: &Type) -> bool {
+    matches!(resolve(env, ty), Type::Opt(_) | Type::Null | Type::Reserved)
+}
+
+/// Whether two definitions are equal once old type names are renamed.
+fn same_definition(old: &Type, new: &Type, renames: &BTreeMap<String, String>) -> bool {
+    old.clone().subst(renames) == *new
+}
+
+pub fn diff(
+    (old_env, old_actor, old_composite): &(TypeEnv, Option<Type>, Composite),
+    (new_env, new_actor, new_composite): &(TypeEnv, Option<Type>, Composite),
+) -> Vec<Change> {
+    let mut renames = BTreeMap::new();
+    let mut changes = Vec::new();
+
+    let removed: Vec<&String> = old_env
+        .0
+        .keys()
+        .filter(|x| !new_env.0.contains_key(*x))
+        .collect();
+    let mut added: Vec<&String> = new_env
+        .0
+        .keys()
+        .filter(|x| !old_env.0.contains_key(*x))
+        .collect();
+
+    // A removed type with the same definition as an added one was renamed
+    for name in removed {
+        let old = &old_env.0[name];
+        match added
+            .iter()
+            .position(|x| same_definition(old, &new_env.0[*x], &renames))
+        {
+            Some(i) => {
+                let new = added.remove(i);
+                renames.insert(name.clone(), new.clone());
+                changes.push(Change::TypeRenamed {
+                    old: name.clone(),
+                    new: new.clone(),
+                });
+            }
+            None => changes.push(Change::TypeRemoved { name: name.clone() }),
+        }
+    }
+    for name in added {
+        changes.push(Change::TypeAdded { name: name.clone() });
+    }
+
+    let mut differ = Differ {
+        old: old_env,
+        new: new_env,
+        old_composite,
+        new_composite,
+        renames,
+        seen: BTreeSet::new(),
+        changes,
+    };
+
+    for (name, old) in old_env.0.iter() {
+        if let Some(new) = new_env.0.get(name) {
+            differ.compare(name, old, new);
+        }
+    }
+
+    match (old_actor, new_actor) {
+        (Some(old), Some(new)) => {
+            match (init_args(old), init_args(new)) {
+                (Some(old_args), Some(new_args)) => differ.compare_list("init", old_args, new_args),
+                (Some(old_args), None) => differ.changes.push(Change::InitRemoved {
+                    args: old_args.len(),
+                }),
+                (None, Some(new_args)) => differ.changes.push(Change::InitAdded {
+                    args: new_args.len(),
+                    breaking: new_args.iter().any(|x| !is_optional(new_env, x)),
+                }),
+                (None, None) => {}
+            }
+            if let (Ok(old), Ok(new)) = (old_env.as_service(old), new_env.as_service(new)) {
+                let composite = (
+                    old_composite.of_actor(old_env, old_actor),
+                    new_composite.of_actor(new_env, new_actor),
+                );
+                differ.compare_service("", old, new, composite);
+            }
+        }
+        (Some(old), None) => {
+            for (name, _) in old_env.as_service(old).unwrap_or(&[]) {
+                differ.changes.push(Change::MethodRemoved {
+                    method: name.clone(),
+                });
+            }
+        }
+        (None, Some(new)) => {
+            for (name, _) in new_env.as_service(new).unwrap_or(&[]) {
+                differ.changes.push(Change::MethodAdded {
+                    method: name.clone(),
+                });
+            }
+        }
+        (None, None) => {}
+    }
+
+    differ.changes
+}
@@ -0,0 +1,196 @@
+//! Doc comments of a Candid file.
+//!
+//! The candid lexer skips comments, so the source between two of its tokens
+//! is scanned for them instead. A block of `//` or `///` lines right in front
+//! of a type, field or method declaration is its doc. A blank line between the
+//! comments and the declaration detaches them.
+
+use std::collections::BTreeMap;
+
+use candid::parser::token::{Token, Tokenizer};
+use serde::Serialize;
+
+#[derive(Default, Serialize, Debug, PartialEq)]
+pub struct DocNode {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, DocNode>,
+}
+
+#[derive(Default, Serialize, Debug)]
+pub struct Docs {
+    pub types: BTreeMap<String, DocNode>,
+    pub methods: BTreeMap<String, DocNode>,
+}
+
+/// Comment lines that end right before `to`. The line `from` is on belongs to
+/// the previous token, so comments trailing it are skipped.
+fn doc_before(src: &str, from: usize, to: usize) -> Option<String> {
+    let mut lines: Vec<&str> = src[from..to].split('\n').collect();
+    if from > 0 {
+        lines.remove(0);
+    }
+    if !lines.pop()?.trim().is_empty() {
+        return None;
+    }
+
+    let mut doc = Vec::new();
+    for line in lines.iter().rev() {
+        let Some(line) = line.trim().strip_prefix("//") else {
+            break;
+        };
+        let line = line.trim_start_matches('/');
+        doc.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+    }
+
+    if doc.is_empty() {
+        None
+    } else {
+        doc.reverse();
+        Some(doc.join("\n"))
+    }
+}
+
+enum Target {
+    Type(Vec<String>),
+    Method,
+    None,
+}
+
+struct Frame {
+    target: Target,
+    /// Variant cases can be declared without a type
+    variant: bool,
+    /// Whether the next label names a field or method
+    expect_label: bool,
+}
+
+impl Docs {
+    pub fn parse(src: &str) -> Docs {
+        let mut docs = Docs::default();
+
+        // Only called on files candid already accepted
+        let tokens: Vec<(usize, Token, usize)> =
+            Tokenizer::new(src).map_while(Result::ok).collect();
+
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut parens = 0;
+        // Path of the type or field whose definition is being read
+        let mut current: Option<Vec<String>> = None;
+        let mut actor = false;
+
+        for (i, (start, token, _)) in tokens.iter().enumerate() {
+            let prev_end = if i > 0 { tokens[i - 1].2 } else { 0 };
+            let prev = i.checked_sub(1).map(|i| &tokens[i].1);
+            let next = tokens.get(i + 1).map(|x| &x.1);
+
+            let label = match token {
+                Token::Id(x) | Token::Text(x) | Token::Decimal(x) => Some(x),
+                _ => None,
+            };
+            let is_label = match stack.last() {
+                Some(frame) if frame.expect_label && parens == 0 && label.is_some() => match next {
+                    Some(Token::Colon) => true,
+                    Some(Token::Semi | Token::RBrace) => frame.variant,
+                    _ => false,
+                },
+                _ => false,
+            };
+
+            match token {
+                Token::Type if stack.is_empty() => {
+                    if let Some((_, Token::Id(name), _)) = tokens.get(i + 1) {
+                        let path = vec![name.clone()];
+                        docs.node(&path).doc = doc_before(src, prev_end, *start);
+                        current = Some(path);
+                    }
+                }
+                Token::Service if stack.is_empty() && current.is_none() => actor = true,
+                _ if is_label => {
+                    let label = label.unwrap();
+                    let doc = doc_before(src, prev_end, *start);
+                    let frame = stack.last_mut().unwrap();
+                    frame.expect_label = false;
+                    match &frame.target {
+                        Target::Type(path) => {
+                            let mut path = path.clone();
+                            path.push(label.clone());
+                            if doc.is_some() {
+                                docs.node(&path).doc = doc;
+                            }
+                            current = Some(path);
+                        }
+                        Target::Method => {
+                            if doc.is_some() {
+                                docs.methods.entry(label.clone()).or_default().doc = doc;
+                            }
+                            current = None;
+                        }
+                        Target::None => {}
+                    }
+                }
+                Token::LBrace => {
+                    let target = if parens > 0 {
+                        Target::None
+                    } else if actor && stack.is_empty() {
+                        Target::Method
+                    } else if matches!(prev, Some(Token::Record | Token::Variant | Token::Service))
+                    {
+                        current.clone().map(Target::Type).unwrap_or(Target::None)
+                    } else {
+                        Target::None
+                    };
+                    stack.push(Frame {
+                        target,
+                        variant: matches!(prev, Some(Token::Variant)),
+                        expect_label: true,
+                    });
+                }
+                Token::RBrace => {
+                    stack.pop();
+                    if stack.is_empty() {
+                        actor = false;
+                    }
+                    current = stack.last().and_then(|f| match &f.target {
+                        Target::Type(path) => Some(path.clone()),
+                        _ => None,
+                    });
+                }
+                Token::Semi if parens == 0 => match stack.last_mut() {
+                    Some(frame) => {
+                        frame.expect_label = true;
+                        if let Target::Type(path) = &frame.target {
+                            current = Some(path.clone());
+                        }
+                    }
+                    None => current = None,
+                },
+                Token::LParen => parens += 1,
+                Token::RParen => parens -= 1,
+                _ => {}
+            }
+        }
+
+        docs.types.retain(|_, node| *node != DocNode::default());
+        docs
+    }
+
+    fn node(&mut self, path: &[String]) -> &mut DocNode {
+        let mut node = self.types.entry(path[0].clone()).or_default();
+        for name in &path[1..] {
+            node = node.fields.entry(name.clone()).or_default();
+        }
+        node
+    }
+
+    /// Adds docs of another file, keeping existing entries
+    pub fn merge(&mut self, other: Docs) {
+        for (name, node) in other.types {
+            self.types.entry(name).or_insert(node);
+        }
+        for (name, node) in other.methods {
+            self.methods.entry(name).or_insert(node);
+        }
+    }
+}
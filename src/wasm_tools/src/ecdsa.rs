@@ -0,0 +1,217 @@
+//! Mock threshold ECDSA of the management canister.
+//!
+//! The master key of every key name is fixed, so signatures and public keys
+//! do not change between runs. Keys are derived from the master key along the
+//! path of the calling canister followed by the derivation path, with the
+//! BIP32-like scheme of the IC: indices are arbitrary byte strings and every
+//! step adds a tweak to the key.
+
+use ciborium::value::Value;
+use hmac::{Hmac, Mac};
+use k256::{
+    ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey, VerifyingKey},
+    elliptic_curve::{sec1::ToEncodedPoint, PrimeField},
+    AffinePoint, NonZeroScalar, ProjectivePoint, Scalar,
+};
+use sha2::Sha512;
+use wasm_bindgen::prelude::*;
+
+use crate::hash_tree::{cbor_bytes, decode_cbor, domain_sep, sha256};
+
+/// Derived public key and its chain code
+pub struct DerivedKey {
+    pub public_key: AffinePoint,
+    pub chain_code: [u8; 32],
+    /// Sum of the tweaks, added to the master secret key
+    pub offset: Scalar,
+}
+
+/// Master secret key of the key named `key_name`.
+pub fn master_key(key_name: &str) -> Result<SigningKey, String> {
+    let secret = sha256(&[&domain_sep("lightic-ecdsa-master-key"), key_name.as_bytes()]);
+    SigningKey::from_slice(&secret).map_err(|x| format!("{x}"))
+}
+
+fn ckd(
+    index: &[u8],
+    point: &AffinePoint,
+    chain_code: &[u8; 32],
+) -> ([u8; 32], Scalar, AffinePoint) {
+    let mut input = point.to_encoded_point(true).as_bytes().to_vec();
+
+    loop {
+        let mut hmac = Hmac::<Sha512>::new_from_slice(chain_code).unwrap();
+        hmac.update(&input);
+        hmac.update(index);
+        let output = hmac.finalize().into_bytes();
+
+        let next_chain_code: [u8; 32] = output[32..].try_into().unwrap();
+        let offset: Option<Scalar> =
+            Scalar::from_repr(*k256::FieldBytes::from_slice(&output[..32])).into();
+        if let Some(offset) = offset {
+            let next =
+                (ProjectivePoint::from(*point) + ProjectivePoint::GENERATOR * offset).to_affine();
+            if next != AffinePoint::IDENTITY {
+                return (next_chain_code, offset, next);
+            }
+        }
+
+        // Out of range tweaks are retried with the next input of SLIP-10,
+        // which replaces the point and keeps the index, as `ckd_pub` of the IC
+        input[0] = 0x01;
+        input[1..].copy_from_slice(&next_chain_code);
+    }
+}
+
+/// Derives along `path` from the master public key, starting from a zero
+/// chain code.
+pub fn derive(master: &VerifyingKey, path: &[Vec<u8>]) -> DerivedKey {
+    let mut key = DerivedKey {
+        public_key: *master.as_affine(),
+        chain_code: [0; 32],
+        offset: Scalar::ZERO,
+    };
+
+    for index in path {
+        let (chain_code, offset, public_key) = ckd(index, &key.public_key, &key.chain_code);
+        key = DerivedKey {
+            public_key,
+            chain_code,
+            offset: key.offset + offset,
+        };
+    }
+
+    key
+}
+
+/// Path of the canister followed by the CBOR array of blobs of its
+/// derivation path.
+pub fn full_path(canister_id: &[u8], derivation_path: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut path = vec![canister_id.to_vec()];
+    match decode_cbor(derivation_path)? {
+        Value::Array(items) => {
+            for item in items.iter() {
+                path.push(cbor_bytes(item)?);
+            }
+        }
+        _ => return Err("Derivation path is not an array".to_string()),
+    }
+    Ok(path)
+}
+
+/// CBOR array of blobs of `items`, the derivation path `full_path` reads.
+#[cfg(test)]
+pub fn cbor_path(items: &[&[u8]]) -> Vec<u8> {
+    let value = Value::Array(items.iter().map(|x| Value::Bytes(x.to_vec())).collect());
+    let mut result = vec![];
+    ciborium::ser::into_writer(&value, &mut result).unwrap();
+    result
+}
+
+/// SEC1 compressed public key followed by the chain code.
+#[wasm_bindgen]
+pub fn ecdsa_public_key(
+    key_name: &str,
+    canister_id: &[u8],
+    derivation_path: &[u8],
+) -> Result<Vec<u8>, String> {
+    let master = master_key(key_name)?;
+    let key = derive(
+        master.verifying_key(),
+        &full_path(canister_id, derivation_path)?,
+    );
+
+    let mut result = key.public_key.to_encoded_point(true).as_bytes().to_vec();
+    result.extend_from_slice(&key.chain_code);
+    Ok(result)
+}
+
+/// Signature of the 32 byte `message_hash` as `r` followed by `s`, with `s`
+/// in its low form as the IC returns it.
+#[wasm_bindgen]
+pub fn sign_with_ecdsa(
+    key_name: &str,
+    canister_id: &[u8],
+    derivation_path: &[u8],
+    message_hash: &[u8],
+) -> Result<Vec<u8>, String> {
+    if message_hash.len() != 32 {
+        return Err("message_hash must be 32 bytes long".to_string());
+    }
+
+    let master = master_key(key_name)?;
+    let key = derive(
+        master.verifying_key(),
+        &full_path(canister_id, derivation_path)?,
+    );
+    let secret: Option<NonZeroScalar> =
+        NonZeroScalar::new(*master.as_nonzero_scalar().as_ref() + key.offset).into();
+    let secret = SigningKey::from(secret.ok_or("Derived key is zero")?);
+
+    let signature: Signature = secret
+        .sign_prehash(message_hash)
+        .map_err(|x| format!("{x}"))?;
+    Ok(signature.to_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::signature::hazmat::PrehashVerifier;
+
+    #[test]
+    fn signatures_verify_with_derived_keys() -> Result<(), String> {
+        let canister_id = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+        let derivation_path = cbor_path(&[b"wallet", &[0, 0, 0, 1]]);
+        let message_hash = sha256(&[b"message"]);
+
+        let key = ecdsa_public_key("dfx_test_key", &canister_id, &derivation_path)?;
+        assert_eq!(key.len(), 65);
+        assert_eq!(
+            key,
+            ecdsa_public_key("dfx_test_key", &canister_id, &derivation_path)?
+        );
+
+        let signature = sign_with_ecdsa(
+            "dfx_test_key",
+            &canister_id,
+            &derivation_path,
+            &message_hash,
+        )?;
+        assert_eq!(signature.len(), 64);
+        let signature = Signature::from_slice(&signature).map_err(|x| format!("{x}"))?;
+        assert!(signature.normalize_s().is_none());
+
+        let public_key = VerifyingKey::from_sec1_bytes(&key[..33]).map_err(|x| format!("{x}"))?;
+        assert!(public_key.verify_prehash(&message_hash, &signature).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_depend_on_the_path() -> Result<(), String> {
+        let canister_id = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+        let empty = ecdsa_public_key("dfx_test_key", &canister_id, &cbor_path(&[]))?;
+        assert_ne!(
+            empty,
+            ecdsa_public_key("dfx_test_key", &canister_id, &cbor_path(&[b"a"]))?
+        );
+        assert_ne!(
+            empty,
+            ecdsa_public_key("dfx_test_key", &[1], &cbor_path(&[]))?
+        );
+        assert_ne!(
+            empty,
+            ecdsa_public_key("test_key_1", &canister_id, &cbor_path(&[]))?
+        );
+
+        let master = master_key("dfx_test_key")?;
+        let derived = derive(master.verifying_key(), &[canister_id.to_vec()]);
+        let public_key = ProjectivePoint::from(*master.verifying_key().as_affine())
+            + ProjectivePoint::GENERATOR * derived.offset;
+        assert_eq!(public_key.to_affine(), derived.public_key);
+
+        assert!(ecdsa_public_key("dfx_test_key", &canister_id, &[0x01]).is_err());
+        assert!(sign_with_ecdsa("dfx_test_key", &canister_id, &cbor_path(&[]), &[0; 31]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,530 @@
+//! Decoding and validation of the envelopes posted to the HTTP interface.
+//!
+//! Rejections carry the HTTP status a replica answers with and the reject code
+//! of the interface specification, `RejectionCode` in `call_context.ts`.
+//!
+//! Calls to the management canister are routed by the `canister_id` field of
+//! their argument. Calls without one, like `raw_rand` or
+//! `provisional_create_canister_with_cycles`, may use any effective canister id.
+
+use candid::{
+    idl_hash,
+    parser::value::{IDLArgs, IDLValue},
+};
+use ciborium::value::Value;
+use serde::Serialize;
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::{
+    authentication::authenticate,
+    hash_tree::{cbor_bytes, decode_cbor},
+    principal::{self_authenticating, ANONYMOUS, MANAGEMENT_CANISTER},
+    request_id::hash_of,
+};
+
+/// How far in the future `ingress_expiry` may be, including clock drift.
+pub const MAX_INGRESS_TTL_NS: u64 = (5 * 60 + 60) * 1_000_000_000;
+pub const MAX_NONCE_BYTES: usize = 32;
+
+const SYS_FATAL: u8 = 1;
+const DESTINATION_INVALID: u8 = 3;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectKind {
+    Malformed,
+    RequestTypeMismatch,
+    IngressExpiry,
+    NonceTooLong,
+    SenderMismatch,
+    InvalidSignature,
+    CanisterIdMismatch,
+    PathForbidden,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Rejection {
+    pub kind: RejectKind,
+    pub status: u16,
+    pub reject_code: u8,
+    pub message: String,
+}
+
+impl Rejection {
+    fn new(kind: RejectKind, message: impl Into<String>) -> Rejection {
+        let (status, reject_code) = match kind {
+            RejectKind::CanisterIdMismatch => (400, DESTINATION_INVALID),
+            RejectKind::PathForbidden => (403, SYS_FATAL),
+            _ => (400, SYS_FATAL),
+        };
+        Rejection {
+            kind,
+            status,
+            reject_code,
+            message: message.into(),
+        }
+    }
+
+    fn malformed(message: impl Into<String>) -> Rejection {
+        Rejection::new(RejectKind::Malformed, message)
+    }
+}
+
+/// Fields of a request content shared by calls, queries and `read_state`.
+#[derive(Serialize, Debug)]
+pub struct Request {
+    pub request_type: String,
+    /// Hex encoded request id
+    pub request_id: String,
+    pub sender: Vec<u8>,
+    pub ingress_expiry: u64,
+    pub canister_id: Option<Vec<u8>>,
+    pub method_name: Option<String>,
+}
+
+fn field<'a>(fields: &'a [(Value, Value)], name: &str) -> Option<&'a Value> {
+    fields
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(name))
+        .map(|(_, v)| v)
+}
+
+fn bytes_field(fields: &[(Value, Value)], name: &str) -> Result<Option<Vec<u8>>, Rejection> {
+    field(fields, name)
+        .map(|v| cbor_bytes(v).map_err(Rejection::malformed))
+        .transpose()
+}
+
+fn text_field(fields: &[(Value, Value)], name: &str) -> Result<Option<String>, Rejection> {
+    field(fields, name)
+        .map(|v| {
+            v.as_text()
+                .map(|t| t.to_string())
+                .ok_or_else(|| Rejection::malformed(format!("{name} is not text")))
+        })
+        .transpose()
+}
+
+fn required<T>(value: Option<T>, name: &str) -> Result<T, Rejection> {
+    value.ok_or_else(|| Rejection::malformed(format!("Missing {name}")))
+}
+
+/// Whether the sender may read `path` through the effective canister id.
+fn path_allowed(path: &[Vec<u8>], effective_canister_id: &[u8]) -> bool {
+    match path {
+        [] => false,
+        [first, rest @ ..] => match (first.as_slice(), rest) {
+            (b"time", []) | (b"subnet", _) | (b"request_status", [_, ..]) => true,
+            (b"canister", [id, field, ..]) => {
+                id == effective_canister_id
+                    && matches!(
+                        field.as_slice(),
+                        b"module_hash" | b"controllers" | b"metadata"
+                    )
+            }
+            _ => false,
+        },
+    }
+}
+
+/// The `canister_id` a management canister call is about, if its argument is
+/// a record with one.
+fn management_target(arg: &[u8]) -> Result<Option<Vec<u8>>, Rejection> {
+    let args = IDLArgs::from_bytes(arg).map_err(|x| Rejection::malformed(format!("{x}")))?;
+    let Some(IDLValue::Record(fields)) = args.args.first() else {
+        return Ok(None);
+    };
+
+    Ok(fields.iter().find_map(|f| match &f.val {
+        IDLValue::Principal(id) if f.id.get_id() == idl_hash("canister_id") => {
+            Some(id.as_slice().to_vec())
+        }
+        _ => None,
+    }))
+}
+
+/// Decodes and validates the envelope posted to the `request_type` endpoint
+/// of `effective_canister_id`. `now` is in nanoseconds since the epoch and
+/// `root_key` is the key canister signatures are certified with.
+/// `request_sender` gives the sender of a known request id, only its sender
+/// may read its `request_status`.
+pub fn validate(
+    body: &[u8],
+    request_type: &str,
+    effective_canister_id: &[u8],
+    root_key: &[u8],
+    now: u64,
+    request_sender: impl Fn(&[u8]) -> Option<Vec<u8>>,
+) -> Result<Request, Rejection> {
+    let value = decode_cbor(body).map_err(Rejection::malformed)?;
+    let envelope = value
+        .as_map()
+        .ok_or_else(|| Rejection::malformed("Envelope is not a map"))?;
+    let content = required(field(envelope, "content"), "content")?;
+    let fields = content
+        .as_map()
+        .ok_or_else(|| Rejection::malformed("Content is not a map"))?;
+
+    let kind = required(text_field(fields, "request_type")?, "request_type")?;
+    if kind != request_type {
+        return Err(Rejection::new(
+            RejectKind::RequestTypeMismatch,
+            format!("Expected a {request_type} request, got {kind}"),
+        ));
+    }
+
+    let ingress_expiry = required(field(fields, "ingress_expiry"), "ingress_expiry")?
+        .as_integer()
+        .and_then(|x| u64::try_from(x).ok())
+        .ok_or_else(|| Rejection::malformed("ingress_expiry is not a nat64"))?;
+    if ingress_expiry < now || ingress_expiry > now.saturating_add(MAX_INGRESS_TTL_NS) {
+        return Err(Rejection::new(
+            RejectKind::IngressExpiry,
+            format!(
+                "ingress_expiry {ingress_expiry} is not within {MAX_INGRESS_TTL_NS}ns after {now}"
+            ),
+        ));
+    }
+
+    if let Some(nonce) = bytes_field(fields, "nonce")? {
+        if nonce.len() > MAX_NONCE_BYTES {
+            return Err(Rejection::new(
+                RejectKind::NonceTooLong,
+                format!(
+                    "Nonce has {} bytes, at most {MAX_NONCE_BYTES} are allowed",
+                    nonce.len()
+                ),
+            ));
+        }
+    }
+
+    let sender = required(bytes_field(fields, "sender")?, "sender")?;
+    let sender_pubkey = bytes_field(envelope, "sender_pubkey")?;
+    let expected = match &sender_pubkey {
+        Some(key) => self_authenticating(key),
+        None => ANONYMOUS.to_vec(),
+    };
+    if sender != expected {
+        let message = match sender_pubkey {
+            Some(_) => "Sender is not derived from sender_pubkey",
+            None => "Sender is not anonymous, but there is no sender_pubkey",
+        };
+        return Err(Rejection::new(RejectKind::SenderMismatch, message));
+    }
+
+    let request_id = hash_of(content).map_err(Rejection::malformed)?;
+    if sender_pubkey.is_some() {
+        authenticate(&value, &request_id, effective_canister_id, root_key, now)
+            .map_err(|x| Rejection::new(RejectKind::InvalidSignature, x))?;
+    }
+
+    let canister_id = bytes_field(fields, "canister_id")?;
+    let method_name = text_field(fields, "method_name")?;
+    if request_type == "read_state" {
+        let paths = required(field(fields, "paths"), "paths")?
+            .as_array()
+            .ok_or_else(|| Rejection::malformed("paths is not an array"))?;
+        for path in paths {
+            let path = path
+                .as_array()
+                .ok_or_else(|| Rejection::malformed("Path is not an array"))?
+                .iter()
+                .map(cbor_bytes)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(Rejection::malformed)?;
+            if !path_allowed(&path, effective_canister_id) {
+                return Err(Rejection::new(
+                    RejectKind::PathForbidden,
+                    format!("Path {path:?} cannot be read through this canister"),
+                ));
+            }
+            if let [label, id, ..] = path.as_slice() {
+                if label == b"request_status" && request_sender(id).is_some_and(|x| x != sender) {
+                    return Err(Rejection::new(
+                        RejectKind::PathForbidden,
+                        "Request status can only be read by the sender of the request",
+                    ));
+                }
+            }
+        }
+    } else {
+        let canister_id = required(canister_id.as_ref(), "canister_id")?;
+        required(method_name.as_ref(), "method_name")?;
+        let arg = required(bytes_field(fields, "arg")?, "arg")?;
+
+        let target = if canister_id == &MANAGEMENT_CANISTER {
+            management_target(&arg)?
+        } else {
+            Some(canister_id.clone())
+        };
+        if target.is_some_and(|x| x != effective_canister_id) {
+            return Err(Rejection::new(
+                RejectKind::CanisterIdMismatch,
+                "canister_id does not match the effective canister id",
+            ));
+        }
+    }
+
+    Ok(Request {
+        request_type: kind,
+        request_id: request_id.iter().map(|b| format!("{b:02x}")).collect(),
+        sender,
+        ingress_expiry,
+        canister_id,
+        method_name,
+    })
+}
+
+/// Validates an envelope, returns the JSON of the request or, as the error,
+/// the JSON of the rejection. `request_sender` is called with a request id and
+/// returns the sender of the request, or `undefined` for unknown requests.
+#[wasm_bindgen]
+pub fn validate_envelope(
+    body: &[u8],
+    request_type: &str,
+    effective_canister_id: &[u8],
+    root_key: &[u8],
+    now: u64,
+    request_sender: &js_sys::Function,
+) -> Result<String, String> {
+    let request_sender = |id: &[u8]| {
+        let id = js_sys::Uint8Array::from(id);
+        let sender = request_sender.call1(&JsValue::NULL, &id).ok()?;
+        sender
+            .dyn_into::<js_sys::Uint8Array>()
+            .ok()
+            .map(|x| x.to_vec())
+    };
+
+    match validate(
+        body,
+        request_type,
+        effective_canister_id,
+        root_key,
+        now,
+        request_sender,
+    ) {
+        Ok(request) => serde_json::to_string(&request).map_err(|x| format!("{x}")),
+        Err(rejection) => Err(serde_json::to_string(&rejection).map_err(|x| format!("{x}"))?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::encode_cbor;
+    use candid::{parser::value::IDLField, types::Label, Principal};
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+    const CANISTER: [u8; 10] = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+
+    fn envelope(content: Vec<(&str, Value)>, sender_pubkey: Option<&[u8]>) -> Vec<u8> {
+        let content = content
+            .into_iter()
+            .map(|(k, v)| (Value::Text(k.into()), v))
+            .collect();
+        let mut fields = vec![(Value::Text("content".into()), Value::Map(content))];
+        if let Some(key) = sender_pubkey {
+            fields.push((
+                Value::Text("sender_pubkey".into()),
+                Value::Bytes(key.to_vec()),
+            ));
+        }
+        encode_cbor(Value::Map(fields)).unwrap()
+    }
+
+    fn call(
+        sender: &[u8],
+        expiry: u64,
+        nonce: &[u8],
+        canister: &[u8],
+    ) -> Vec<(&'static str, Value)> {
+        vec![
+            ("request_type", "call".into()),
+            ("sender", Value::Bytes(sender.to_vec())),
+            ("ingress_expiry", expiry.into()),
+            ("nonce", Value::Bytes(nonce.to_vec())),
+            ("canister_id", Value::Bytes(canister.to_vec())),
+            ("method_name", "greet".into()),
+            ("arg", Value::Bytes(b"DIDL\x00\x00".to_vec())),
+        ]
+    }
+
+    fn no_requests(_: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn kind(result: Result<Request, Rejection>) -> RejectKind {
+        result.unwrap_err().kind
+    }
+
+    #[test]
+    fn validate_call() {
+        let expiry = NOW + 60_000_000_000;
+        let body = envelope(call(&ANONYMOUS, expiry, &[1; 8], &CANISTER), None);
+        let request = validate(&body, "call", &CANISTER, &[], NOW, no_requests).unwrap();
+        assert_eq!(request.method_name.as_deref(), Some("greet"));
+        assert_eq!(request.request_id.len(), 64);
+
+        let key = b"some DER encoded public key";
+        let body = envelope(
+            call(&self_authenticating(key), expiry, &[], &CANISTER),
+            Some(key),
+        );
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::InvalidSignature
+        );
+
+        // Management canister calls go to the canister in their argument
+        let body = envelope(call(&ANONYMOUS, expiry, &[], &MANAGEMENT_CANISTER), None);
+        assert!(validate(&body, "call", &CANISTER, &[], NOW, no_requests).is_ok());
+
+        let management_call = |target: &[u8]| {
+            let arg = IDLArgs {
+                args: vec![IDLValue::Record(vec![IDLField {
+                    id: Label::Named("canister_id".into()),
+                    val: IDLValue::Principal(Principal::from_slice(target)),
+                }])],
+            }
+            .to_bytes()
+            .unwrap();
+            let mut content = call(&ANONYMOUS, expiry, &[], &MANAGEMENT_CANISTER);
+            content.retain(|(k, _)| *k != "arg");
+            content.push(("arg", Value::Bytes(arg)));
+            envelope(content, None)
+        };
+        let body = management_call(&CANISTER);
+        assert!(validate(&body, "call", &CANISTER, &[], NOW, no_requests).is_ok());
+        let body = management_call(&[0, 0, 0, 0, 0, 0, 0, 2, 1, 1]);
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::CanisterIdMismatch
+        );
+
+        assert_eq!(
+            kind(validate(&body, "query", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::RequestTypeMismatch
+        );
+
+        let late = NOW + MAX_INGRESS_TTL_NS + 1;
+        let body = envelope(call(&ANONYMOUS, late, &[], &CANISTER), None);
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::IngressExpiry
+        );
+        let body = envelope(call(&ANONYMOUS, NOW - 1, &[], &CANISTER), None);
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::IngressExpiry
+        );
+
+        let body = envelope(call(&ANONYMOUS, expiry, &[1; 33], &CANISTER), None);
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::NonceTooLong
+        );
+
+        let body = envelope(
+            call(&self_authenticating(key), expiry, &[], &CANISTER),
+            None,
+        );
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::SenderMismatch
+        );
+        let body = envelope(call(&ANONYMOUS, expiry, &[], &CANISTER), Some(key));
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::SenderMismatch
+        );
+
+        let other = [0, 0, 0, 0, 0, 0, 0, 2, 1, 1];
+        let body = envelope(call(&ANONYMOUS, expiry, &[], &CANISTER), None);
+        let rejection = validate(&body, "call", &other, &[], NOW, no_requests).unwrap_err();
+        assert_eq!(rejection.kind, RejectKind::CanisterIdMismatch);
+        assert_eq!(rejection.reject_code, DESTINATION_INVALID);
+
+        let mut content = call(&ANONYMOUS, expiry, &[], &CANISTER);
+        content.retain(|(k, _)| *k != "method_name");
+        let body = envelope(content, None);
+        assert_eq!(
+            kind(validate(&body, "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::Malformed
+        );
+        assert_eq!(
+            kind(validate(b"\xff", "call", &CANISTER, &[], NOW, no_requests)),
+            RejectKind::Malformed
+        );
+    }
+
+    #[test]
+    fn validate_read_state() {
+        let read_state = |paths: Vec<Vec<&[u8]>>| {
+            let paths = paths
+                .into_iter()
+                .map(|p| Value::Array(p.into_iter().map(|l| Value::Bytes(l.to_vec())).collect()))
+                .collect();
+            envelope(
+                vec![
+                    ("request_type", "read_state".into()),
+                    ("sender", Value::Bytes(ANONYMOUS.to_vec())),
+                    ("ingress_expiry", (NOW + 1).into()),
+                    ("paths", Value::Array(paths)),
+                ],
+                None,
+            )
+        };
+
+        let body = read_state(vec![
+            vec![b"time"],
+            vec![b"request_status", &[0xab; 32], b"status"],
+            vec![b"canister", &CANISTER, b"module_hash"],
+        ]);
+        assert!(validate(&body, "read_state", &CANISTER, &[], NOW, no_requests).is_ok());
+
+        let other = [0, 0, 0, 0, 0, 0, 0, 2, 1, 1];
+        let body = read_state(vec![vec![b"canister", &other, b"module_hash"]]);
+        let rejection =
+            validate(&body, "read_state", &CANISTER, &[], NOW, no_requests).unwrap_err();
+        assert_eq!(rejection.kind, RejectKind::PathForbidden);
+        assert_eq!(rejection.status, 403);
+
+        let body = read_state(vec![vec![b"secrets"]]);
+        assert_eq!(
+            kind(validate(
+                &body,
+                "read_state",
+                &CANISTER,
+                &[],
+                NOW,
+                no_requests
+            )),
+            RejectKind::PathForbidden
+        );
+
+        // Only the sender of a request reads its status
+        let body = read_state(vec![vec![b"request_status", &[0xab; 32], b"status"]]);
+        let sent_by = |sender: &'static [u8]| move |_: &[u8]| Some(sender.to_vec());
+        assert!(validate(
+            &body,
+            "read_state",
+            &CANISTER,
+            &[],
+            NOW,
+            sent_by(&ANONYMOUS)
+        )
+        .is_ok());
+        assert_eq!(
+            kind(validate(
+                &body,
+                "read_state",
+                &CANISTER,
+                &[],
+                NOW,
+                sent_by(&[1, 2, 3])
+            )),
+            RejectKind::PathForbidden
+        );
+    }
+}
@@ -0,0 +1,610 @@
+//! Hash trees of the IC interface specification, as used in certificates.
+//!
+//! The exported functions take and return hash trees as CBOR, in the array
+//! form agents use, so `HashTree` values of `@dfinity/agent` round-trip.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ciborium::value::Value;
+use miracl_core_bls12381::hash256::HASH256;
+use wasm_bindgen::prelude::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HashTree {
+    Empty,
+    Fork(Box<HashTree>, Box<HashTree>),
+    Labeled(Vec<u8>, Box<HashTree>),
+    Leaf(Vec<u8>),
+    Pruned([u8; 32]),
+}
+
+/// Length prefixed domain separator, `domain_sep` in `bls.ts`
+pub fn domain_sep(s: &str) -> Vec<u8> {
+    let mut sep = vec![s.len() as u8];
+    sep.extend_from_slice(s.as_bytes());
+    sep
+}
+
+pub fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hash = HASH256::new();
+    for part in parts {
+        hash.process_array(part);
+    }
+    hash.hash()
+}
+
+/// Decodes a CBOR item, dropping the self-describing tag.
+pub fn decode_cbor(data: &[u8]) -> Result<Value, String> {
+    match ciborium::de::from_reader(data).map_err(|x| format!("{x}"))? {
+        Value::Tag(55799, value) => Ok(*value),
+        value => Ok(value),
+    }
+}
+
+/// Encodes a CBOR item with the self-describing tag in front.
+pub fn encode_cbor(value: Value) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    ciborium::ser::into_writer(&Value::Tag(55799, Box::new(value)), &mut data)
+        .map_err(|x| format!("{x}"))?;
+    Ok(data)
+}
+
+/// Bytes of a CBOR byte string, text is taken as UTF-8
+pub fn cbor_bytes(value: &Value) -> Result<Vec<u8>, String> {
+    match value {
+        Value::Bytes(b) => Ok(b.clone()),
+        Value::Text(t) => Ok(t.as_bytes().to_vec()),
+        _ => Err(format!("Expected bytes, got {value:?}")),
+    }
+}
+
+impl HashTree {
+    pub fn digest(&self) -> [u8; 32] {
+        match self {
+            HashTree::Empty => sha256(&[&domain_sep("ic-hashtree-empty")]),
+            HashTree::Fork(l, r) => {
+                sha256(&[&domain_sep("ic-hashtree-fork"), &l.digest(), &r.digest()])
+            }
+            HashTree::Labeled(label, t) => {
+                sha256(&[&domain_sep("ic-hashtree-labeled"), label, &t.digest()])
+            }
+            HashTree::Leaf(v) => sha256(&[&domain_sep("ic-hashtree-leaf"), v]),
+            HashTree::Pruned(h) => *h,
+        }
+    }
+
+    pub fn to_cbor(&self) -> Value {
+        let items = match self {
+            HashTree::Empty => vec![0.into()],
+            HashTree::Fork(l, r) => vec![1.into(), l.to_cbor(), r.to_cbor()],
+            HashTree::Labeled(label, t) => vec![2.into(), Value::Bytes(label.clone()), t.to_cbor()],
+            HashTree::Leaf(v) => vec![3.into(), Value::Bytes(v.clone())],
+            HashTree::Pruned(h) => vec![4.into(), Value::Bytes(h.to_vec())],
+        };
+        Value::Array(items)
+    }
+
+    pub fn from_cbor(value: &Value) -> Result<HashTree, String> {
+        let invalid = || format!("Invalid hash tree node {value:?}");
+
+        let items = value.as_array().ok_or_else(invalid)?;
+        let tag: u8 = items
+            .first()
+            .and_then(|x| x.as_integer())
+            .and_then(|x| x.try_into().ok())
+            .ok_or_else(invalid)?;
+
+        let tree = match (tag, &items[1..]) {
+            (0, []) => HashTree::Empty,
+            (1, [l, r]) => HashTree::Fork(
+                Box::new(HashTree::from_cbor(l)?),
+                Box::new(HashTree::from_cbor(r)?),
+            ),
+            (2, [label, t]) => {
+                HashTree::Labeled(cbor_bytes(label)?, Box::new(HashTree::from_cbor(t)?))
+            }
+            (3, [v]) => HashTree::Leaf(cbor_bytes(v)?),
+            (4, [h]) => HashTree::Pruned(cbor_bytes(h)?.try_into().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+
+        Ok(tree)
+    }
+
+    fn pruned(&self) -> HashTree {
+        HashTree::Pruned(self.digest())
+    }
+
+    /// Labeled subtrees directly below this node, looking through forks.
+    fn children(&self) -> Vec<(&[u8], &HashTree)> {
+        match self {
+            HashTree::Fork(l, r) => {
+                let mut children = l.children();
+                children.extend(r.children());
+                children
+            }
+            HashTree::Labeled(label, t) => vec![(label, t)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Value of the leaf at `path`, none if it is missing or pruned.
+    pub fn lookup(&self, path: &[&[u8]]) -> Option<&[u8]> {
+        match (path, self) {
+            ([], HashTree::Leaf(v)) => Some(v),
+            ([], _) => None,
+            ([label, rest @ ..], _) => self
+                .children()
+                .into_iter()
+                .find(|(l, _)| l == label)
+                .and_then(|(_, t)| t.lookup(rest)),
+        }
+    }
+
+    /// Witness for `paths`: the tree with every subtree outside of them pruned.
+    /// The tree under a requested path is kept whole. Requested labels that
+    /// are missing keep the labels next to them, so the witness proves their
+    /// absence.
+    pub fn witness(&self, paths: &[Vec<Vec<u8>>]) -> HashTree {
+        if paths.iter().any(|p| p.is_empty()) {
+            return self.clone();
+        }
+
+        let mut requests: BTreeMap<&[u8], Vec<Vec<Vec<u8>>>> = BTreeMap::new();
+        for path in paths {
+            requests
+                .entry(&path[0])
+                .or_default()
+                .push(path[1..].to_vec());
+        }
+
+        let labels: Vec<&[u8]> = self.children().into_iter().map(|(l, _)| l).collect();
+        let mut neighbours = BTreeSet::new();
+        for label in requests.keys() {
+            if labels.contains(label) {
+                continue;
+            }
+            let next = labels.partition_point(|l| l < label);
+            if next > 0 {
+                neighbours.insert(labels[next - 1]);
+            }
+            if let Some(l) = labels.get(next) {
+                neighbours.insert(*l);
+            }
+        }
+
+        self.witness_children(&requests, &neighbours)
+    }
+
+    fn witness_children(
+        &self,
+        requests: &BTreeMap<&[u8], Vec<Vec<Vec<u8>>>>,
+        neighbours: &BTreeSet<&[u8]>,
+    ) -> HashTree {
+        match self {
+            HashTree::Fork(l, r) => {
+                let l = l.witness_children(requests, neighbours);
+                let r = r.witness_children(requests, neighbours);
+                match (&l, &r) {
+                    (HashTree::Pruned(_), HashTree::Pruned(_)) => self.pruned(),
+                    _ => HashTree::Fork(Box::new(l), Box::new(r)),
+                }
+            }
+            HashTree::Labeled(label, t) => match requests.get(label.as_slice()) {
+                Some(paths) => HashTree::Labeled(label.clone(), Box::new(t.witness(paths))),
+                None if neighbours.contains(label.as_slice()) => {
+                    HashTree::Labeled(label.clone(), Box::new(t.pruned()))
+                }
+                None => self.pruned(),
+            },
+            HashTree::Leaf(_) => self.pruned(),
+            HashTree::Empty | HashTree::Pruned(_) => self.clone(),
+        }
+    }
+
+    /// Merges two witnesses of the same tree, keeping what either reveals.
+    pub fn merge(&self, other: &HashTree) -> Result<HashTree, String> {
+        let tree = match (self, other) {
+            (HashTree::Pruned(h), t) | (t, HashTree::Pruned(h)) => {
+                if t.digest() != *h {
+                    return Err("Cannot merge witnesses of different trees".to_string());
+                }
+                t.clone()
+            }
+            (HashTree::Fork(l1, r1), HashTree::Fork(l2, r2)) => {
+                HashTree::Fork(Box::new(l1.merge(l2)?), Box::new(r1.merge(r2)?))
+            }
+            (HashTree::Labeled(l1, t1), HashTree::Labeled(l2, t2)) if l1 == l2 => {
+                HashTree::Labeled(l1.clone(), Box::new(t1.merge(t2)?))
+            }
+            (HashTree::Empty, HashTree::Empty) => HashTree::Empty,
+            (HashTree::Leaf(v1), HashTree::Leaf(v2)) if v1 == v2 => HashTree::Leaf(v1.clone()),
+            _ => return Err("Cannot merge witnesses of different trees".to_string()),
+        };
+
+        Ok(tree)
+    }
+}
+
+/// Forks over `items` as a balanced binary tree, keeping their order.
+fn fork(mut items: Vec<HashTree>) -> HashTree {
+    match items.len() {
+        0 => HashTree::Empty,
+        1 => items.pop().unwrap(),
+        n => {
+            let right = items.split_off(n / 2);
+            HashTree::Fork(Box::new(fork(items)), Box::new(fork(right)))
+        }
+    }
+}
+
+/// Tree of labeled values built by path, like `Tree` in `hash_tree.ts`. As in
+/// the hash tree, a node holds either a value or children.
+#[derive(Default, Clone, Debug)]
+pub struct LabeledTree {
+    pub value: Option<Vec<u8>>,
+    pub children: BTreeMap<Vec<u8>, LabeledTree>,
+}
+
+impl LabeledTree {
+    /// Reads back a tree built by [`LabeledTree::to_hash_tree`], which must not
+    /// be pruned.
+    pub fn from_hash_tree(tree: &HashTree) -> Result<LabeledTree, String> {
+        let mut node = LabeledTree::default();
+        match tree {
+            HashTree::Empty => {}
+            HashTree::Leaf(v) => node.value = Some(v.clone()),
+            HashTree::Fork(l, r) => {
+                node.children = LabeledTree::from_hash_tree(l)?.children;
+                node.children
+                    .extend(LabeledTree::from_hash_tree(r)?.children);
+            }
+            HashTree::Labeled(label, t) => {
+                node.children
+                    .insert(label.clone(), LabeledTree::from_hash_tree(t)?);
+            }
+            HashTree::Pruned(_) => return Err("Cannot insert into a pruned tree".to_string()),
+        }
+
+        Ok(node)
+    }
+
+    /// Sets `value` at `path`, fails when a prefix of `path` holds a value or
+    /// the node at `path` has children.
+    pub fn insert(&mut self, path: &[Vec<u8>], value: Vec<u8>) -> Result<(), String> {
+        let mut node = self;
+        for (i, label) in path.iter().enumerate() {
+            if node.value.is_some() {
+                return Err(format!(
+                    "Cannot insert {} below the value at {}",
+                    path_string(path),
+                    path_string(&path[..i])
+                ));
+            }
+            node = node.children.entry(label.clone()).or_default();
+        }
+        if !node.children.is_empty() {
+            return Err(format!(
+                "Cannot set a value at {}, it has children",
+                path_string(path)
+            ));
+        }
+
+        node.value = Some(value);
+        Ok(())
+    }
+
+    /// Reads a CBOR array of `[path, value]` pairs, where `path` is an array of
+    /// labels.
+    pub fn from_cbor(value: &Value) -> Result<LabeledTree, String> {
+        let mut tree = LabeledTree::default();
+
+        let pairs = value
+            .as_array()
+            .ok_or("Expected an array of [path, value] pairs")?;
+        for pair in pairs {
+            let Some([path, value]) = pair.as_array().map(|x| x.as_slice()) else {
+                return Err(format!("Expected a [path, value] pair, got {pair:?}"));
+            };
+            let path = path
+                .as_array()
+                .ok_or_else(|| format!("Expected a path, got {path:?}"))?
+                .iter()
+                .map(cbor_bytes)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            tree.insert(&path, cbor_bytes(value)?)?;
+        }
+
+        Ok(tree)
+    }
+
+    /// Labels are sorted, as lookups in agents expect.
+    pub fn to_hash_tree(&self) -> HashTree {
+        if !self.children.is_empty() {
+            let items = self
+                .children
+                .iter()
+                .map(|(label, t)| HashTree::Labeled(label.clone(), Box::new(t.to_hash_tree())))
+                .collect();
+            return fork(items);
+        }
+
+        match &self.value {
+            Some(v) => HashTree::Leaf(v.clone()),
+            None => HashTree::Empty,
+        }
+    }
+}
+
+fn path_string(path: &[Vec<u8>]) -> String {
+    let labels: Vec<_> = path.iter().map(|l| String::from_utf8_lossy(l)).collect();
+    format!("/{}", labels.join("/"))
+}
+
+fn decode_tree(tree: &[u8]) -> Result<HashTree, String> {
+    HashTree::from_cbor(&decode_cbor(tree)?)
+}
+
+fn decode_path(value: &Value) -> Result<Vec<Vec<u8>>, String> {
+    value
+        .as_array()
+        .ok_or_else(|| format!("Expected a path, got {value:?}"))?
+        .iter()
+        .map(cbor_bytes)
+        .collect()
+}
+
+/// Sets `value` at `path` of `tree`, like `Tree.insertValue` in `hash_tree.ts`,
+/// and returns the new tree.
+#[wasm_bindgen]
+pub fn hash_tree_insert(tree: &[u8], path: &[u8], value: &[u8]) -> Result<Vec<u8>, String> {
+    let mut labeled = LabeledTree::from_hash_tree(&decode_tree(tree)?)?;
+    labeled.insert(&decode_path(&decode_cbor(path)?)?, value.to_vec())?;
+
+    encode_cbor(labeled.to_hash_tree().to_cbor())
+}
+
+/// Builds the tree of the CBOR array of `[path, value]` pairs in `pairs` at
+/// once, like `certificate_build` does.
+#[wasm_bindgen]
+pub fn hash_tree_build(pairs: &[u8]) -> Result<Vec<u8>, String> {
+    let tree = LabeledTree::from_cbor(&decode_cbor(pairs)?)?.to_hash_tree();
+
+    encode_cbor(tree.to_cbor())
+}
+
+#[wasm_bindgen]
+pub fn hash_tree_digest(tree: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(decode_tree(tree)?.digest().to_vec())
+}
+
+/// Prunes `tree` down to the CBOR array of label arrays in `paths`.
+#[wasm_bindgen]
+pub fn hash_tree_witness(tree: &[u8], paths: &[u8]) -> Result<Vec<u8>, String> {
+    let paths = decode_cbor(paths)?
+        .as_array()
+        .ok_or("Expected an array of paths")?
+        .iter()
+        .map(decode_path)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    encode_cbor(decode_tree(tree)?.witness(&paths).to_cbor())
+}
+
+#[wasm_bindgen]
+pub fn hash_tree_merge(tree1: &[u8], tree2: &[u8]) -> Result<Vec<u8>, String> {
+    let tree = decode_tree(tree1)?.merge(&decode_tree(tree2)?)?;
+
+    encode_cbor(tree.to_cbor())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn labeled(label: &str, t: HashTree) -> HashTree {
+        HashTree::Labeled(label.as_bytes().to_vec(), Box::new(t))
+    }
+
+    fn leaf(v: &str) -> HashTree {
+        HashTree::Leaf(v.as_bytes().to_vec())
+    }
+
+    fn fork(l: HashTree, r: HashTree) -> HashTree {
+        HashTree::Fork(Box::new(l), Box::new(r))
+    }
+
+    fn spec_example() -> HashTree {
+        fork(
+            fork(
+                labeled(
+                    "a",
+                    fork(
+                        fork(labeled("x", leaf("hello")), HashTree::Empty),
+                        labeled("y", leaf("world")),
+                    ),
+                ),
+                labeled("b", leaf("good")),
+            ),
+            fork(labeled("c", HashTree::Empty), labeled("d", leaf("morning"))),
+        )
+    }
+
+    fn path(labels: &[&str]) -> Vec<Vec<u8>> {
+        labels.iter().map(|l| l.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn spec_example_digest() -> Result<(), String> {
+        // Example tree of the interface specification
+        let tree = spec_example();
+
+        assert_eq!(
+            tree.digest(),
+            hex!("eb5c5b2195e62d996b84c9bcc8259d19a83786a2f59e0878cec84c811f669aa0")
+        );
+
+        let decoded = HashTree::from_cbor(&decode_cbor(&encode_cbor(tree.to_cbor())?)?)?;
+        assert_eq!(decoded, tree);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lookup() {
+        let tree = spec_example();
+
+        assert_eq!(tree.lookup(&[b"a", b"y"]), Some(b"world".as_ref()));
+        assert_eq!(tree.lookup(&[b"d"]), Some(b"morning".as_ref()));
+        assert_eq!(tree.lookup(&[b"c"]), None);
+        assert_eq!(tree.lookup(&[b"a"]), None);
+
+        let witness = tree.witness(&[path(&["b"])]);
+        assert_eq!(witness.lookup(&[b"b"]), Some(b"good".as_ref()));
+        assert_eq!(witness.lookup(&[b"d"]), None);
+    }
+
+    #[test]
+    fn witness() {
+        let tree = spec_example();
+        let pruned = |t: HashTree| HashTree::Pruned(t.digest());
+
+        let witness = tree.witness(&[path(&["a", "x"])]);
+        assert_eq!(
+            witness,
+            fork(
+                fork(
+                    labeled(
+                        "a",
+                        fork(
+                            fork(labeled("x", leaf("hello")), HashTree::Empty),
+                            pruned(labeled("y", leaf("world"))),
+                        ),
+                    ),
+                    pruned(labeled("b", leaf("good"))),
+                ),
+                pruned(fork(
+                    labeled("c", HashTree::Empty),
+                    labeled("d", leaf("morning"))
+                )),
+            )
+        );
+        assert_eq!(witness.digest(), tree.digest());
+
+        // "bb" is missing, "b" and "c" around it are kept
+        let witness = tree.witness(&[path(&["bb"])]);
+        assert_eq!(
+            witness,
+            fork(
+                fork(
+                    pruned(labeled(
+                        "a",
+                        fork(
+                            fork(labeled("x", leaf("hello")), HashTree::Empty),
+                            labeled("y", leaf("world")),
+                        ),
+                    )),
+                    labeled("b", pruned(leaf("good"))),
+                ),
+                fork(
+                    labeled("c", pruned(HashTree::Empty)),
+                    pruned(labeled("d", leaf("morning")))
+                ),
+            )
+        );
+        assert_eq!(witness.digest(), tree.digest());
+
+        assert_eq!(tree.witness(&[vec![]]), tree);
+    }
+
+    #[test]
+    fn merge() -> Result<(), String> {
+        let tree = spec_example();
+
+        let a = tree.witness(&[path(&["a", "x"])]);
+        let d = tree.witness(&[path(&["d"])]);
+        assert_eq!(
+            a.merge(&d)?,
+            tree.witness(&[path(&["a", "x"]), path(&["d"])])
+        );
+        assert_eq!(a.merge(&tree)?, tree);
+
+        let other = labeled("a", leaf("hello"));
+        assert!(a.merge(&other).is_err());
+        assert!(a.merge(&HashTree::Pruned([0; 32])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn insert() -> Result<(), String> {
+        let cbor_path = |labels: &[&str]| {
+            let labels = labels.iter().map(|l| Value::Text(l.to_string())).collect();
+            encode_cbor(Value::Array(labels))
+        };
+
+        let tree = encode_cbor(HashTree::Empty.to_cbor())?;
+        let tree = hash_tree_insert(&tree, &cbor_path(&["time"])?, &[1])?;
+        let tree = hash_tree_insert(&tree, &cbor_path(&["a", "y"])?, b"world")?;
+        let tree = hash_tree_insert(&tree, &cbor_path(&["a", "x"])?, b"hello")?;
+        let tree = hash_tree_insert(&tree, &cbor_path(&["a", "x"])?, b"again")?;
+
+        assert_eq!(
+            decode_tree(&tree)?,
+            fork(
+                labeled(
+                    "a",
+                    fork(labeled("x", leaf("again")), labeled("y", leaf("world")))
+                ),
+                labeled("time", HashTree::Leaf(vec![1])),
+            )
+        );
+
+        let pruned = decode_tree(&tree)?.witness(&[path(&["time"])]);
+        let pruned = encode_cbor(pruned.to_cbor())?;
+        assert!(hash_tree_insert(&pruned, &cbor_path(&["b"])?, b"").is_err());
+
+        let pair = |labels: &[&str], value: &[u8]| {
+            let labels = labels.iter().map(|l| Value::Text(l.to_string())).collect();
+            Value::Array(vec![Value::Array(labels), Value::Bytes(value.to_vec())])
+        };
+        let pairs = Value::Array(vec![
+            pair(&["time"], &[1]),
+            pair(&["a", "y"], b"world"),
+            pair(&["a", "x"], b"hello"),
+            pair(&["a", "x"], b"again"),
+        ]);
+        assert_eq!(hash_tree_build(&encode_cbor(pairs)?)?, tree);
+
+        Ok(())
+    }
+
+    #[test]
+    fn labeled_tree() -> Result<(), String> {
+        let mut tree = LabeledTree::default();
+        tree.insert(&[b"time".to_vec()], vec![1])?;
+        tree.insert(&[b"a".to_vec(), b"y".to_vec()], b"world".to_vec())?;
+        tree.insert(&[b"a".to_vec(), b"x".to_vec()], b"hello".to_vec())?;
+
+        assert_eq!(
+            tree.to_hash_tree(),
+            fork(
+                labeled(
+                    "a",
+                    fork(labeled("x", leaf("hello")), labeled("y", leaf("world")))
+                ),
+                labeled("time", HashTree::Leaf(vec![1])),
+            )
+        );
+
+        // A node cannot hold both a value and children
+        assert!(tree.insert(&[b"a".to_vec()], b"value".to_vec()).is_err());
+        assert!(tree
+            .insert(&[b"time".to_vec(), b"x".to_vec()], b"value".to_vec())
+            .is_err());
+
+        Ok(())
+    }
+}
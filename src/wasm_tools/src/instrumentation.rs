@@ -201,7 +201,7 @@ const TABLE_STR: &str = "table";
 //     MAX_WASM_MEMORY_IN_BYTES / (PAGE_SIZE as u64) / (WASM_PAGE_SIZE as u64);
 
 // const MAX_STABLE_MEMORY_IN_WASM_PAGES: u64 = MAX_STABLE_MEMORY_IN_BYTES / (WASM_PAGE_SIZE as u64);
-/// There is one byte for each OS page in the stable memory.
+// /// There is one byte for each OS page in the stable memory.
 // const STABLE_BYTEMAP_SIZE_IN_WASM_PAGES: u64 = MAX_STABLE_MEMORY_IN_WASM_PAGES / (PAGE_SIZE as u64);
 
 // fn add_type(module: &mut Module, ty: Type) -> u32 {
@@ -341,11 +341,11 @@ const TABLE_STR: &str = "table";
 //     pub start_fn_ix: Option<u32>,
 // }
 
-/// Takes a Wasm binary and inserts the instructions metering and memory grow
-/// instrumentation.
-///
-/// Returns an [`InstrumentationOutput`] or an error if the input binary could
-/// not be instrumented.
+// /// Takes a Wasm binary and inserts the instructions metering and memory grow
+// /// instrumentation.
+// ///
+// /// Returns an [`InstrumentationOutput`] or an error if the input binary could
+// /// not be instrumented.
 // pub(super) fn instrument(
 //     module: Module<'_>,
 //     cost_to_compile_wasm_instruction: NumInstructions,
@@ -559,15 +559,15 @@ const TABLE_STR: &str = "table";
 // Helper function used by instrumentation to export additional symbols.
 //
 // Returns the new module or panics in debug mode if a symbol is not reserved.
-#[doc(hidden)] // pub for usage in tests
-               // pub fn export_additional_symbols<'a>(
-               //     mut module: Module<'a>,
-               //     export_module_data: &ExportModuleData,
-               //     extra_data: &'a mut Option<Vec<u8>>,
-               //     wasm_native_stable_memory: FlagStatus,
-               //     stable_memory_bytemap_index: u32,
-               // ) -> Module<'a> {
-               //     // push function to decrement the instruction counter
+// #[doc(hidden)] // pub for usage in tests
+// pub fn export_additional_symbols<'a>(
+//     mut module: Module<'a>,
+//     export_module_data: &ExportModuleData,
+//     extra_data: &'a mut Option<Vec<u8>>,
+//     wasm_native_stable_memory: FlagStatus,
+//     stable_memory_bytemap_index: u32,
+// ) -> Module<'a> {
+//     // push function to decrement the instruction counter
 
 //     let func_type = Type::Func(FuncType::new([ValType::I32], [ValType::I32]));
 
@@ -0,0 +1,139 @@
+//! Checks of a Candid interface against the module that implements it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{types::Type, TypeEnv};
+use serde::Serialize;
+use wasmparser::ExternalKind;
+
+use crate::{modes::Mode, wasm_transform::Module};
+
+const EXPORT_MODES: [Mode; 3] = [Mode::Update, Mode::Query, Mode::CompositeQuery];
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ModeMismatch {
+    pub method: String,
+    pub declared: &'static str,
+    pub exported: Vec<&'static str>,
+}
+
+/// Number of declared init arguments, reported when the module has no
+/// `canister_init` export to take them. A `canister_init` without declared
+/// arguments is fine, it may set up state without taking any.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct InitMismatch {
+    pub args: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+    /// Service methods the module does not export
+    pub missing_exports: Vec<String>,
+    /// Exported methods the service does not declare
+    pub undeclared_exports: Vec<String>,
+    pub mode_mismatches: Vec<ModeMismatch>,
+    pub init: Option<InitMismatch>,
+}
+
+/// Modes every method is exported with, keyed by method name.
+pub fn exported_methods(module: &Module) -> BTreeMap<String, Vec<Mode>> {
+    let mut methods: BTreeMap<String, Vec<Mode>> = BTreeMap::new();
+
+    for export in module.exports.iter() {
+        if export.kind != ExternalKind::Func {
+            continue;
+        }
+        let Some((prefix, name)) = export.name.split_once(' ') else {
+            continue;
+        };
+        if let Some(mode) = EXPORT_MODES.iter().find(|m| m.export_prefix() == prefix) {
+            methods.entry(name.to_string()).or_default().push(*mode);
+        }
+    }
+
+    methods
+}
+
+/// Service methods that are exported, but not with the prefix of their
+/// declared mode. Methods without any export are not reported.
+pub fn mode_mismatches(
+    module: &Module,
+    env: &TypeEnv,
+    actor: &Option<Type>,
+    composite: &BTreeSet<String>,
+) -> Vec<ModeMismatch> {
+    let exports = exported_methods(module);
+    let Some(Ok(service)) = actor.as_ref().map(|x| env.as_service(x)) else {
+        return Vec::new();
+    };
+
+    let mut mismatches = Vec::new();
+    for (name, ty) in service {
+        let (Ok(func), Some(exported)) = (env.as_func(ty), exports.get(name)) else {
+            continue;
+        };
+
+        let declared = Mode::of_method(name, func, composite);
+        if !exported
+            .iter()
+            .any(|m| m.export_prefix() == declared.export_prefix())
+        {
+            mismatches.push(ModeMismatch {
+                method: name.clone(),
+                declared: declared.name(),
+                exported: exported.iter().map(|m| m.name()).collect(),
+            });
+        }
+    }
+
+    mismatches
+}
+
+/// Compares the service and init arguments of a Candid interface with the
+/// exports of the module. Methods starting with `__`, like the candid
+/// interface hack of the Rust CDK, are internal and never declared.
+pub fn check(
+    module: &Module,
+    env: &TypeEnv,
+    actor: &Option<Type>,
+    composite: &BTreeSet<String>,
+) -> Report {
+    let exports = exported_methods(module);
+    let service = match actor.as_ref().map(|x| env.as_service(x)) {
+        Some(Ok(service)) => service,
+        _ => &[],
+    };
+
+    let missing_exports = service
+        .iter()
+        .filter(|(name, _)| !exports.contains_key(name))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let undeclared_exports = exports
+        .iter()
+        .filter(|(name, _)| !name.starts_with("__") && !service.iter().any(|(m, _)| m == *name))
+        .flat_map(|(name, modes)| {
+            modes
+                .iter()
+                .map(move |m| format!("{} {name}", m.export_prefix()))
+        })
+        .collect();
+
+    let args = match actor {
+        Some(Type::Class(args, _)) => args.len(),
+        _ => 0,
+    };
+    let exported = module
+        .exports
+        .iter()
+        .any(|e| e.kind == ExternalKind::Func && e.name == "canister_init");
+    let init = (args > 0 && !exported).then_some(InitMismatch { args });
+
+    Report {
+        missing_exports,
+        undeclared_exports,
+        mode_mismatches: mode_mismatches(module, env, actor, composite),
+        init,
+    }
+}
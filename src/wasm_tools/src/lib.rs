@@ -1,15 +1,37 @@
+use std::collections::BTreeMap;
+
 use candid::{
     check_prog,
     parser::{
-        types::IDLType,
+        types::{Dec, IDLType},
         typing::{check_type, Env},
     },
     types::Type,
     IDLProg, TypeEnv,
 };
 
+mod account;
+mod authentication;
 mod bls;
+mod certificate;
+mod cost;
+mod diff;
+mod doc_comments;
+mod ecdsa;
+mod envelope;
+mod hash_tree;
+mod interface;
+mod mock_reply;
+mod modes;
+mod principal;
+mod random;
+mod request_id;
+mod schnorr;
+mod target_candid;
 mod target_json;
+mod target_json_schema;
+mod target_rust;
+mod topology;
 
 fn check_actor(env: &Env, actor: &Option<IDLType>) -> Result<Option<Type>, candid::error::Error> {
     match actor {
@@ -31,7 +53,8 @@ fn check_actor(env: &Env, actor: &Option<IDLType>) -> Result<Option<Type>, candi
     }
 }
 
-fn check_file_(prog: &str) -> Result<(TypeEnv, Option<Type>), candid::error::Error> {
+fn check_file_(prog: &str) -> Result<(TypeEnv, Option<Type>, Composite), candid::error::Error> {
+    let (prog, composite) = modes::strip_composite_queries(prog);
     let prog = prog.parse::<IDLProg>()?;
 
     let mut te = TypeEnv::new();
@@ -44,32 +67,290 @@ fn check_file_(prog: &str) -> Result<(TypeEnv, Option<Type>), candid::error::Err
     };
 
     let actor = check_actor(&env, &prog.actor)?;
-    Ok((te, actor))
+    Ok((te, actor, composite))
+}
+
+/// Resolves `import` relative to the directory of the importing file.
+fn resolve_import(from: &str, import: &str) -> String {
+    let mut parts: Vec<&str> = match from.rfind('/') {
+        Some(i) if !import.starts_with('/') => from[..i].split('/').collect(),
+        _ => Vec::new(),
+    };
+
+    for part in import.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    parts.join("/")
 }
 
+fn normalize_path(path: &str) -> String {
+    resolve_import("", path)
+}
+
+/// Collects type declarations of `name` and everything it imports, imported
+/// declarations come first. `stack` holds the current import chain and is used
+/// to report cycles. The composite queries of imported service types are
+/// merged into those of `name`.
+fn load_imports(
+    files: &BTreeMap<String, String>,
+    name: &str,
+    stack: &mut Vec<String>,
+    loaded: &mut Vec<String>,
+    decs: &mut Vec<Dec>,
+) -> Result<(IDLProg, Composite), String> {
+    if let Some(i) = stack.iter().position(|x| x == name) {
+        let mut cycle = stack[i..].to_vec();
+        cycle.push(name.to_string());
+        return Err(format!("Cyclic import: {}", cycle.join(" -> ")));
+    }
+
+    let code = files
+        .get(name)
+        .ok_or_else(|| format!("Cannot find imported file {name}"))?;
+    let (code, mut composite) = modes::strip_composite_queries(code);
+    let prog = code
+        .parse::<IDLProg>()
+        .map_err(|x| format!("{name}: {x}"))?;
+
+    stack.push(name.to_string());
+    for dec in prog.decs.iter() {
+        if let Dec::ImportD(import) = dec {
+            let path = resolve_import(name, import);
+            if !loaded.contains(&path) {
+                let (imported, imported_composite) =
+                    load_imports(files, &path, stack, loaded, decs)?;
+                composite.types.extend(imported_composite.types);
+                decs.extend(
+                    imported
+                        .decs
+                        .into_iter()
+                        .filter(|x| matches!(x, Dec::TypD(_))),
+                );
+            }
+        }
+    }
+    stack.pop();
+    loaded.push(name.to_string());
+
+    Ok((prog, composite))
+}
+
+/// Same as `check_file_`, for a set of files that import each other. `files`
+/// is a JSON object of file name to contents, only the service of `root` is
+/// kept.
+fn check_files_(files: &str, root: &str) -> Result<(TypeEnv, Option<Type>, Composite), String> {
+    let files: BTreeMap<String, String> =
+        serde_json::from_str(files).map_err(|x| format!("{x}"))?;
+    let files: BTreeMap<String, String> = files
+        .into_iter()
+        .map(|(k, v)| (normalize_path(&k), v))
+        .collect();
+
+    let mut decs = Vec::new();
+    let (prog, composite) = load_imports(
+        &files,
+        &normalize_path(root),
+        &mut Vec::new(),
+        &mut Vec::new(),
+        &mut decs,
+    )?;
+
+    decs.extend(prog.decs.into_iter().filter(|x| matches!(x, Dec::TypD(_))));
+    let prog = IDLProg {
+        decs,
+        actor: prog.actor,
+    };
+
+    let mut te = TypeEnv::new();
+    check_prog(&mut te, &prog).map_err(|x| format!("{x}"))?;
+
+    let env = Env {
+        te: &mut te,
+        pre: false,
+    };
+
+    let actor = check_actor(&env, &prog.actor).map_err(|x| format!("{x}"))?;
+    Ok((te, actor, composite))
+}
+
+use doc_comments::Docs;
 use instrumentation::export_table;
+use modes::Composite;
 use wasm_bindgen::prelude::*;
 use wasm_transform::Module;
 
 #[wasm_bindgen]
 pub fn parse_candid_to_js(data: &str) -> Result<String, String> {
-    let (env, actor) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
 
     Ok(candid::bindings::javascript::compile(&env, &actor))
 }
 
 #[wasm_bindgen]
 pub fn parse_candid_to_ts(data: &str) -> Result<String, String> {
-    let (env, actor) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
 
     Ok(candid::bindings::typescript::compile(&env, &actor))
 }
 
+#[wasm_bindgen]
+pub fn parse_candid_to_ts_with_imports(files: &str, root: &str) -> Result<String, String> {
+    let (env, actor, _) = check_files_(files, root)?;
+
+    Ok(candid::bindings::typescript::compile(&env, &actor))
+}
+
+#[wasm_bindgen]
+pub fn parse_candid_to_json_schema(data: &str, openapi: bool) -> Result<String, String> {
+    let (env, actor, composite) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let composite = composite.of_actor(&env, &actor);
+
+    let schema = if openapi {
+        target_json_schema::compile_openapi(&env, &actor, composite, "Candid service")
+    } else {
+        target_json_schema::compile(&env, &actor, composite)
+    };
+
+    Ok(schema.to_string())
+}
+
+#[wasm_bindgen]
+pub fn parse_candid_to_rust(data: &str, config: Option<String>) -> Result<String, String> {
+    let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+
+    let config: target_rust::Config = match config {
+        Some(config) => serde_json::from_str(&config).map_err(|x| format!("{x}"))?,
+        None => Default::default(),
+    };
+
+    target_rust::compile(&env, &actor, &config)
+}
+
+#[wasm_bindgen]
+pub fn parse_candid_to_motoko(data: &str) -> Result<String, String> {
+    let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+
+    Ok(candid::bindings::motoko::compile(&env, &actor))
+}
+
+#[wasm_bindgen]
+pub fn format_candid(data: &str, config: Option<String>) -> Result<String, String> {
+    let (env, actor, composite) = check_file_(data).map_err(|x| format!("{x}"))?;
+
+    let config: target_candid::Config = match config {
+        Some(config) => serde_json::from_str(&config).map_err(|x| format!("{x}"))?,
+        None => Default::default(),
+    };
+    let docs = if config.comments {
+        Docs::parse(data)
+    } else {
+        Docs::default()
+    };
+
+    Ok(target_candid::compile(env, actor, &docs, &composite))
+}
+
 #[wasm_bindgen]
 pub fn parse_candid(data: &str) -> Result<String, String> {
-    let (env, actor) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let (env, actor, composite) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let docs = Docs::parse(data);
+
+    Ok(target_json::compile(&env, &actor, &docs, &composite))
+}
+
+#[wasm_bindgen]
+pub fn parse_candid_with_imports(files: &str, root: &str) -> Result<String, String> {
+    let (env, actor, composite) = check_files_(files, root)?;
+
+    // Methods can only come from the root service, so its docs go first
+    let sources: BTreeMap<String, String> =
+        serde_json::from_str(files).map_err(|x| format!("{x}"))?;
+    let mut docs = Docs::default();
+    if let Some((_, data)) = sources
+        .iter()
+        .find(|(k, _)| normalize_path(k) == normalize_path(root))
+    {
+        docs.merge(Docs::parse(data));
+    }
+    for data in sources.values() {
+        let mut imported = Docs::parse(data);
+        imported.methods.clear();
+        docs.merge(imported);
+    }
+
+    Ok(target_json::compile(&env, &actor, &docs, &composite))
+}
+
+#[wasm_bindgen]
+pub fn candid_diff(old_data: &str, new_data: &str) -> Result<String, String> {
+    let old = check_file_(old_data).map_err(|x| format!("{x}"))?;
+    let new = check_file_(new_data).map_err(|x| format!("{x}"))?;
+
+    let changes = diff::diff(&old, &new);
 
-    Ok(target_json::compile(&env, &actor))
+    Ok(serde_json::json!({ "changes": changes }).to_string())
+}
+
+#[wasm_bindgen]
+pub fn candid_random_args(
+    data: &str,
+    method: &str,
+    seed: u64,
+    config: Option<String>,
+) -> Result<Vec<u8>, String> {
+    let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let actor = actor.ok_or("Candid file does not define a service")?;
+    let func = env.get_method(&actor, method).map_err(|x| format!("{x}"))?;
+
+    let config: random::Config = match config {
+        Some(config) => serde_json::from_str(&config).map_err(|x| format!("{x}"))?,
+        None => Default::default(),
+    };
+
+    random::random_args(&env, &func.args, seed, &config)
+}
+
+#[wasm_bindgen]
+pub fn candid_default_reply(
+    data: &str,
+    method: &str,
+    hints: Option<String>,
+) -> Result<Vec<u8>, String> {
+    let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+    let actor = actor.ok_or("Candid file does not define a service")?;
+    let func = env.get_method(&actor, method).map_err(|x| format!("{x}"))?;
+
+    let hints: Option<serde_json::Value> = match hints {
+        Some(hints) => Some(serde_json::from_str(&hints).map_err(|x| format!("{x}"))?),
+        None => None,
+    };
+
+    mock_reply::reply(&env, &func.rets, hints.as_ref())
+}
+
+#[wasm_bindgen]
+pub fn candid_message_cost(
+    method: &str,
+    args: &[u8],
+    config: Option<String>,
+) -> Result<String, String> {
+    candid::IDLArgs::from_bytes(args).map_err(|x| format!("{x}"))?;
+
+    let config: cost::Config = match config {
+        Some(config) => serde_json::from_str(&config).map_err(|x| format!("{x}"))?,
+        None => Default::default(),
+    };
+
+    let report = cost::estimate(method, args, &config)?;
+
+    serde_json::to_string(&report).map_err(|x| format!("{x}"))
 }
 
 mod instrumentation;
@@ -86,11 +367,381 @@ pub fn wasm_instrument(data: &[u8]) -> Result<Vec<u8>, String> {
     Ok(enc)
 }
 
+#[wasm_bindgen]
+pub fn check_interface(wasm: &[u8], data: &str) -> Result<String, String> {
+    let module = Module::parse(wasm, false).map_err(|x| format!("{x}"))?;
+    let (env, actor, composite) = check_file_(data).map_err(|x| format!("{x}"))?;
+
+    let report = interface::check(&module, &env, &actor, composite.of_actor(&env, &actor));
+
+    serde_json::to_string(&report).map_err(|x| format!("{x}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::Value;
 
+    #[test]
+    fn parse_test_recursive() -> Result<(), String> {
+        let data = "type List = opt record { head : nat; tail : List };
+        type Tree = variant { leaf : int; node : Forest };
+        type Forest = vec Tree;
+        type Value = variant { Nat : nat; Text : text; Array : vec Value; Map : vec record { text; Value } };
+        type Plain = record { list : List; value : Value };
+        service : { get : (Plain) -> (Tree) query }";
+
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        assert_eq!(
+            v["recs"],
+            serde_json::json!(["Forest", "List", "Tree", "Value"])
+        );
+        assert_eq!(v["types"]["List"]["Opt"]["Record"]["tail"]["Rec"], "List");
+        assert_eq!(v["types"]["Tree"]["Variant"]["node"]["Rec"], "Forest");
+        assert_eq!(v["types"]["Forest"]["Vec"]["Rec"], "Tree");
+        assert_eq!(
+            v["types"]["Value"]["Variant"]["Map"]["Vec"]["Tuple"][1]["Rec"],
+            "Value"
+        );
+        assert_eq!(v["types"]["Plain"]["Record"]["list"]["Rec"], "List");
+        assert_eq!(
+            v["actor"]["Spec"]["Service"]["get"]["Func"]["args"][0]["Var"],
+            "Plain"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_test_empty_reserved() -> Result<(), String> {
+        let data = "type Unused = record { never : empty; legacy : reserved };
+        service : { stop : (reserved) -> (empty) }";
+
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        assert_eq!(v["types"]["Unused"]["Record"]["never"], "empty");
+        assert_eq!(v["types"]["Unused"]["Record"]["legacy"], "reserved");
+        assert_eq!(
+            v["actor"]["Spec"]["Service"]["stop"]["Func"]["args"][0],
+            "reserved"
+        );
+        assert_eq!(
+            v["actor"]["Spec"]["Service"]["stop"]["Func"]["rets"][0],
+            "empty"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_to_rust_test() -> Result<(), String> {
+        let data = "type Tokens = record { e8s : nat64 };
+        type SendArgs = record { to : text; fee : Tokens; memo : nat64; from_subaccount : opt vec nat8 };
+        type List = opt record { head : nat; tail : List };
+        service : { send_dfx : (SendArgs) -> (nat64); list : () -> (List) query }";
+
+        let result = parse_candid_to_rust(data, None)?;
+        assert!(result.contains("use ic_cdk::export::candid::{self, CandidType, Deserialize};"));
+        assert!(result.contains("struct SendArgs {"));
+        assert!(result.contains("struct SERVICE(candid::Principal);"));
+        assert!(
+            result.contains("pub async fn send_dfx(&self, arg0: SendArgs) -> CallResult<(u64,)>")
+        );
+        assert!(result.contains("Box<List>"));
+
+        let config = r#"{ "candid_crate": "candid", "service_name": "Ledger", "public": true }"#;
+        let result = parse_candid_to_rust(data, Some(config.to_string()))?;
+        assert!(result.contains("use candid::{self, CandidType, Deserialize};"));
+        assert!(result.contains("pub struct SendArgs {"));
+        assert!(result.contains("pub from_subaccount: Option<Vec<u8>>"));
+        assert!(result.contains("pub struct Ledger(pub candid::Principal);"));
+        assert!(result.contains("impl Ledger"));
+
+        let result = parse_candid_to_rust(data, Some(r#"{ "stubs": false }"#.to_string()))?;
+        assert!(!result.contains("SERVICE"));
+        assert!(!result.contains("CallResult"));
+
+        assert!(parse_candid_to_rust(data, Some("{ \"public\": 1 }".to_string())).is_err());
+
+        let variants = "type Result = variant { Ok : record { nat; text }; Err : variant { \"type\" : text; Other } };
+        type Tree = variant { leaf : int; node : vec Tree };
+        service : { \"run-it\" : (record { \"self\" : bool; items : vec record { id : nat } }) -> (Result, Tree) }";
+        for data in [data, variants] {
+            for config in [None, Some(config.to_string())] {
+                let result = parse_candid_to_rust(data, config)?;
+                syn::parse_file(&result).map_err(|x| format!("{x}: {result}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_to_motoko_test() -> Result<(), String> {
+        let data = "type Tokens = record { e8s : nat64 };
+        service : { balance : (text) -> (Tokens) query; send : (Tokens) -> () }";
+
+        let result = parse_candid_to_motoko(data)?;
+        assert!(result.contains("public type Tokens = { e8s : Nat64 };"));
+        assert!(result.contains("balance : shared query Text -> async Tokens;"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn random_args_test() -> Result<(), String> {
+        use candid::parser::value::{IDLArgs, IDLValue};
+
+        let data = "type List = opt record { head : int; tail : List };
+        type Tree = variant { leaf : nat8; node : vec Tree };
+        type Args = record { to : principal; memo : blob; note : opt text; amount : nat };
+        service : {
+          send : (Args, Tree) -> ();
+          push : (List, vec List) -> ();
+          nothing : (empty) -> ();
+        }";
+
+        let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+        let actor = actor.unwrap();
+
+        for method in ["send", "push"] {
+            let types = &env.get_method(&actor, method).unwrap().args;
+
+            for seed in 0..200 {
+                let config = r#"{ "depth": 3, "size": 20, "width": 4 }"#.to_string();
+                let bytes = candid_random_args(data, method, seed, Some(config.clone()))?;
+                let again = candid_random_args(data, method, seed, Some(config))?;
+                assert_eq!(bytes, again);
+
+                let args = IDLArgs::from_bytes_with_types(&bytes, &env, types)
+                    .map_err(|x| format!("{x}"))?;
+                assert_eq!(args.args.len(), 2);
+
+                if let IDLValue::Vec(items) = &args.args[1] {
+                    assert!(items.len() <= 4);
+                }
+            }
+        }
+
+        assert_ne!(
+            candid_random_args(data, "send", 1, None)?,
+            candid_random_args(data, "send", 2, None)?
+        );
+        assert!(candid_random_args(data, "nothing", 0, None).is_err());
+        assert!(candid_random_args(data, "missing", 0, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_imports_test() -> Result<(), String> {
+        let files = serde_json::json!({
+            "main.did": "import \"types/ledger.did\"; import \"common.did\";
+              service : { transfer : (TransferArgs) -> (Result) }",
+            "common.did": "type Result = variant { Ok : nat; Err : text };",
+            "types/ledger.did": "import \"../common.did\"; import \"./account.did\";
+              type TransferArgs = record { to : Account; amount : nat; last : opt Result };",
+            "types/account.did": "type Account = record { owner : principal; subaccount : opt blob };",
+        })
+        .to_string();
+
+        let result = parse_candid_with_imports(&files, "main.did")?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        assert_eq!(v["types"]["TransferArgs"]["Record"]["to"]["Var"], "Account");
+        assert!(v["types"]["Account"].is_object());
+        assert!(v["types"]["Result"].is_object());
+        assert!(v["actor"]["Spec"]["Service"]["transfer"].is_object());
+
+        let ts = parse_candid_to_ts_with_imports(&files, "./main.did")?;
+        assert!(ts.contains("export interface Account"));
+
+        let cyclic = serde_json::json!({
+            "a.did": "import \"b.did\"; service : {}",
+            "b.did": "import \"c.did\"; type B = nat;",
+            "c.did": "import \"b.did\"; type C = nat;",
+        })
+        .to_string();
+        let err = parse_candid_with_imports(&cyclic, "a.did").unwrap_err();
+        assert_eq!(err, "Cyclic import: b.did -> c.did -> b.did");
+
+        let missing = serde_json::json!({ "a.did": "import \"b.did\"; service : {}" }).to_string();
+        assert!(parse_candid_with_imports(&missing, "a.did").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_docs_test() -> Result<(), String> {
+        let data = "/// A ledger account
+        /// identified by its owner
+        type Account = record {
+          /// Principal owning the account
+          owner : principal;
+          subaccount : opt blob; // not a doc
+          // Nested fields keep their path
+          meta : record {
+            /// Display name
+            name : text };
+        };
+
+        // Detached by the blank line
+
+        type Result = variant {
+          // Transfer went through
+          Ok : nat;
+          /// Nothing happened
+          Noop;
+          Err : text;
+        };
+        service : {
+          /// Balance of an account
+          balance : (Account) -> (nat) query;
+          // Moves tokens, arguments are
+          // not documented
+          transfer : (record { /* ignored */ to : Account; amount : nat }) -> (Result);
+          plain : () -> ();
+        }";
+
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert!(v["docs"].is_null());
+
+        let account = &v["types"]["Account"];
+        assert_eq!(account["doc"], "A ledger account\nidentified by its owner");
+        let fields = &account["Record"];
+        assert_eq!(fields["owner"]["Prim"], "principal");
+        assert_eq!(fields["owner"]["doc"], "Principal owning the account");
+        assert_eq!(fields["subaccount"]["Opt"]["Vec"], "nat8");
+        assert!(fields["subaccount"]["doc"].is_null());
+        assert_eq!(fields["meta"]["doc"], "Nested fields keep their path");
+        assert_eq!(fields["meta"]["Record"]["name"]["Prim"], "text");
+        assert_eq!(fields["meta"]["Record"]["name"]["doc"], "Display name");
+
+        let result = &v["types"]["Result"];
+        assert!(result["doc"].is_null());
+        assert_eq!(result["Variant"]["Ok"]["doc"], "Transfer went through");
+        assert_eq!(result["Variant"]["Noop"]["Prim"], Value::Null);
+        assert_eq!(result["Variant"]["Noop"]["doc"], "Nothing happened");
+        assert_eq!(result["Variant"]["Err"], "text");
+
+        let methods = &v["actor"]["Spec"]["Service"];
+        assert_eq!(methods["balance"]["doc"], "Balance of an account");
+        assert_eq!(methods["balance"]["Func"]["mode"], "query");
+        assert_eq!(
+            methods["transfer"]["doc"],
+            "Moves tokens, arguments are\nnot documented"
+        );
+        assert!(methods["transfer"]["Func"]["args"][0]["Record"]["to"]["doc"].is_null());
+        assert!(methods["plain"]["doc"].is_null());
+
+        let docs = Docs::parse(data);
+        assert_eq!(docs.types.keys().collect::<Vec<_>>(), ["Account", "Result"]);
+        assert!(!docs.methods.contains_key("plain"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_hashes_test() -> Result<(), String> {
+        let data = "type Account = record { owner : principal; subaccount : opt blob; 42 : nat };
+        type Result = variant { Ok : record { nat; text }; Err : text };
+        service : { transfer : (record { to : Account }) -> (Result) }";
+
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        let owner = candid::idl_hash("owner");
+        let account = &v["types"]["Account"];
+        assert_eq!(account["hashes"]["owner"], owner);
+        assert_eq!(account["hashes"]["42"], 42);
+        assert_eq!(v["types"]["Result"]["hashes"]["Ok"], candid::idl_hash("Ok"));
+        assert!(v["types"]["Result"]["Variant"]["Ok"]["Tuple"].is_array());
+
+        let hashes = &v["hashes"];
+        assert_eq!(hashes[owner.to_string()], "owner");
+        assert_eq!(hashes[candid::idl_hash("Err").to_string()], "Err");
+        assert_eq!(hashes[candid::idl_hash("to").to_string()], "to");
+        assert!(hashes["42"].is_null());
+        assert!(hashes["0"].is_null());
+
+        // Quoted labels are escaped in the JSON
+        let data = r#"type Quoted = record { "a\"b" : nat };
+        service : { "get \"it\"" : () -> (Quoted) query }"#;
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        let quoted = &v["types"]["Quoted"];
+        assert_eq!(quoted["Record"]["a\"b"], "nat");
+        assert_eq!(quoted["hashes"]["a\"b"], candid::idl_hash("a\"b"));
+        assert_eq!(v["hashes"][candid::idl_hash("a\"b").to_string()], "a\"b");
+        assert!(v["actor"]["Spec"]["Service"]["get \"it\""].is_object());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_json_schema_test() -> Result<(), String> {
+        let data = "type Account = record { owner : principal; subaccount : opt blob; memo : Memo; fee : Fee };
+        type Memo = opt nat64;
+        type Fee = nat;
+        type Result = variant { Ok : nat; Err : text };
+        type List = opt record { nat8; List };
+        service : {
+          transfer : (Account, nat64) -> (Result);
+          balance : (Account) -> (nat) query;
+          list : () -> (List) query;
+          notify : (text) -> () oneway;
+        }";
+
+        let result = parse_candid_to_json_schema(data, false)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        let account = &v["$defs"]["Account"];
+        assert_eq!(account["type"], "object");
+        assert_eq!(account["properties"]["owner"]["format"], "principal");
+        assert_eq!(
+            account["properties"]["subaccount"]["anyOf"][0]["contentEncoding"],
+            "base64"
+        );
+        assert_eq!(account["required"], serde_json::json!(["fee", "owner"]));
+        assert_eq!(account["properties"]["memo"]["$ref"], "#/$defs/Memo");
+
+        let variant = &v["$defs"]["Result"]["oneOf"];
+        assert_eq!(variant[0]["required"], serde_json::json!(["Ok"]));
+        assert_eq!(variant[0]["properties"]["Ok"]["type"], "string");
+
+        let list = &v["$defs"]["List"]["anyOf"][0];
+        assert_eq!(list["prefixItems"][1]["$ref"], "#/$defs/List");
+
+        let transfer = &v["methods"]["transfer"];
+        assert_eq!(transfer["mode"], "update");
+        assert_eq!(
+            transfer["args"]["prefixItems"][0]["$ref"],
+            "#/$defs/Account"
+        );
+        assert_eq!(transfer["args"]["prefixItems"][1]["format"], "nat64");
+        assert_eq!(v["methods"]["balance"]["mode"], "query");
+        assert_eq!(v["methods"]["notify"]["mode"], "oneway");
+
+        let result = parse_candid_to_json_schema(data, true)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        assert_eq!(v["openapi"], "3.1.0");
+        let post = &v["paths"]["/transfer"]["post"];
+        assert_eq!(post["operationId"], "transfer");
+        assert_eq!(
+            post["requestBody"]["content"]["application/json"]["schema"]["prefixItems"][0]["$ref"],
+            "#/components/schemas/Account"
+        );
+        assert!(v["paths"]["/notify"]["post"]["responses"]["200"]["content"].is_null());
+        assert!(v["components"]["schemas"]["Result"]["oneOf"].is_array());
+
+        Ok(())
+    }
+
     #[test]
     fn parse_test_4() -> Result<(), String> {
         let data = "type WhitelistSlot = 
@@ -938,4 +1589,433 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn candid_diff_test() -> Result<(), String> {
+        let old = "type Account = record { owner : principal; subaccount : opt blob };
+        type Memo = blob;
+        type Args = record { to : Account; amount : nat; memo : opt Memo };
+        service : {
+          transfer : (Args) -> (nat);
+          balance : (Account) -> (nat) query;
+          burn : (nat) -> ();
+        }";
+        let new =
+            "type Account = record { owner : principal; subaccount : blob; label : opt text };
+        type Note = blob;
+        type Args = record { to : Account; amount : nat; memo : opt Note };
+        service : {
+          transfer : (Args) -> (nat);
+          balance : (Account) -> (nat);
+          mint : (nat) -> ();
+        }";
+
+        let result = candid_diff(old, new)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        let changes = v["changes"].as_array().unwrap();
+        let has = |change: Value| changes.contains(&change);
+
+        assert!(has(
+            serde_json::json!({ "kind": "type_renamed", "old": "Memo", "new": "Note" })
+        ));
+        assert!(has(
+            serde_json::json!({ "kind": "field_added", "path": "Account", "field": "label" })
+        ));
+        assert!(has(serde_json::json!({
+            "kind": "optional_changed", "path": "Account.subaccount", "optional": false
+        })));
+        assert!(has(serde_json::json!({
+            "kind": "mode_changed", "method": "balance", "old": "query", "new": "update"
+        })));
+        assert!(has(
+            serde_json::json!({ "kind": "method_added", "method": "mint" })
+        ));
+        assert!(has(
+            serde_json::json!({ "kind": "method_removed", "method": "burn" })
+        ));
+        // Renamed aliases do not show up as changed fields
+        assert_eq!(changes.len(), 6);
+
+        let result = candid_diff(old, old)?;
+        assert_eq!(result, r#"{"changes":[]}"#);
+
+        // Init args are reported when the whole list comes or goes
+        let result = candid_diff(
+            "service : { f : () -> () }",
+            "service : (nat) -> { f : () -> () }",
+        )?;
+        assert_eq!(
+            result,
+            r#"{"changes":[{"args":1,"breaking":true,"kind":"init_added"}]}"#
+        );
+        let result = candid_diff(
+            "service : { f : () -> () }",
+            "service : (opt nat) -> { f : () -> () }",
+        )?;
+        assert_eq!(
+            result,
+            r#"{"changes":[{"args":1,"breaking":false,"kind":"init_added"}]}"#
+        );
+        let result = candid_diff(
+            "service : (nat) -> { f : () -> () }",
+            "service : { f : () -> () }",
+        )?;
+        assert_eq!(result, r#"{"changes":[{"args":1,"kind":"init_removed"}]}"#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_candid_test() -> Result<(), String> {
+        let data = "type Owner = Account;
+        /// Ledger account
+        type Account = record {
+          /// Subaccount, defaults to zero
+          subaccount : opt vec nat8; owner : principal };
+        type Init = record { minter : Account };
+        type Minter = record { owner : principal; subaccount : opt blob };
+        service : (opt Init) -> {
+            // Current balance
+            balance : (Owner) -> (nat) query;
+            transfer : (Minter, nat) -> ();
+        }";
+
+        let result = format_candid(data, None)?;
+        assert_eq!(
+            result,
+            "type Account = record { owner : principal; subaccount : opt blob };
+type Init = record { minter : Account };
+type Minter = record { owner : principal; subaccount : opt blob };
+service : (opt Init) -> {
+  balance : (Account) -> (nat) query;
+  transfer : (Minter, nat) -> ();
+}
+"
+        );
+
+        let result = format_candid(data, Some(r#"{"comments": true}"#.to_string()))?;
+        assert_eq!(
+            result,
+            "// Ledger account
+type Account = record {
+  owner : principal;
+  // Subaccount, defaults to zero
+  subaccount : opt blob;
+};
+type Init = record { minter : Account };
+type Minter = record { owner : principal; subaccount : opt blob };
+service : (opt Init) -> {
+  // Current balance
+  balance : (Account) -> (nat) query;
+  transfer : (Minter, nat) -> ();
+}
+"
+        );
+
+        // Formatting is idempotent
+        assert_eq!(format_candid(&result, None)?, format_candid(data, None)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_reply_test() -> Result<(), String> {
+        use candid::parser::value::IDLArgs;
+
+        let data = "type Tree = variant { node : record { Tree; Tree }; leaf : nat };
+        type Account = record { owner : principal; subaccount : opt blob };
+        type Result = variant { Ok : nat; Err : variant { Insufficient : record { balance : nat }; Other } };
+        type Loop = record { next : Loop };
+        service : {
+          info : () -> (Account, vec text, Tree, float64) query;
+          transfer : (nat) -> (Result);
+          loop : () -> (Loop);
+          check : () -> (variant { Err : text; Ok : nat }) query;
+        }";
+        let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+        let actor = actor.unwrap();
+
+        let decode = |method: &str, bytes: &[u8]| {
+            let types = &env.get_method(&actor, method).unwrap().rets;
+            IDLArgs::from_bytes_with_types(bytes, &env, types)
+                .map(|x| x.to_string())
+                .map_err(|x| format!("{x}"))
+        };
+
+        let bytes = candid_default_reply(data, "info", None)?;
+        assert_eq!(
+            decode("info", &bytes)?,
+            r#"(
+  record { owner = principal "aaaaa-aa"; subaccount = null },
+  vec {},
+  variant { leaf = 0 : nat },
+  0 : float64,
+)"#
+        );
+
+        let hints = r#"[{ "subaccount": "AQI=" }, ["a", "b"], { "leaf": "7" }, 1.5]"#;
+        let bytes = candid_default_reply(data, "info", Some(hints.to_string()))?;
+        assert_eq!(
+            decode("info", &bytes)?,
+            r#"(
+  record { owner = principal "aaaaa-aa"; subaccount = opt blob "\01\02" },
+  vec { "a"; "b" },
+  variant { leaf = 7 : nat },
+  1.5 : float64,
+)"#
+        );
+
+        let hints = r#"[{ "Err": { "Insufficient": { "balance": 10 } } }]"#;
+        let bytes = candid_default_reply(data, "transfer", Some(hints.to_string()))?;
+        assert_eq!(
+            decode("transfer", &bytes)?,
+            "(variant { Err = variant { Insufficient = record { balance = 10 : nat } } })"
+        );
+
+        // The first case is picked in label hash order, not declaration order
+        assert!(candid::idl_hash("Ok") < candid::idl_hash("Err"));
+        let bytes = candid_default_reply(data, "check", None)?;
+        assert_eq!(decode("check", &bytes)?, "(variant { Ok = 0 : nat })");
+
+        let hints = r#"[{ "subaccount": "AQI" }]"#.to_string();
+        assert!(candid_default_reply(data, "info", Some(hints)).is_err());
+        let hints = r#"[{ "Ok": 1, "Err": "Other" }]"#.to_string();
+        assert!(candid_default_reply(data, "transfer", Some(hints)).is_err());
+        let hints = r#"[{ "amount": 1 }]"#.to_string();
+        assert!(candid_default_reply(data, "info", Some(hints)).is_err());
+        assert!(candid_default_reply(data, "loop", None).is_err());
+
+        // A recursive case first in hash order is not picked
+        let data = "type Tree = variant { branch : record { Tree; Tree }; leaf };
+        service : { get : () -> (Tree) query }";
+        assert!(candid::idl_hash("branch") < candid::idl_hash("leaf"));
+        let bytes = candid_default_reply(data, "get", None)?;
+        let (env, actor, _) = check_file_(data).map_err(|x| format!("{x}"))?;
+        let actor = actor.unwrap();
+        let types = &env.get_method(&actor, "get").unwrap().rets;
+        let reply =
+            IDLArgs::from_bytes_with_types(&bytes, &env, types).map_err(|x| format!("{x}"))?;
+        assert_eq!(reply.to_string(), "(variant { leaf })");
+
+        Ok(())
+    }
+
+    /// Module exporting an empty function under every name in `exports`.
+    fn test_module(exports: &[&str]) -> Vec<u8> {
+        use wasm_encoder::{
+            CodeSection, ExportKind, ExportSection, Function, FunctionSection, Instruction,
+            TypeSection,
+        };
+
+        let mut types = TypeSection::new();
+        types.function([], []);
+        let mut functions = FunctionSection::new();
+        functions.function(0);
+        let mut export_section = ExportSection::new();
+        for name in exports {
+            export_section.export(name, ExportKind::Func, 0);
+        }
+        let mut code = CodeSection::new();
+        let mut body = Function::new([]);
+        body.instruction(&Instruction::End);
+        code.function(&body);
+
+        let mut module = wasm_encoder::Module::new();
+        module
+            .section(&types)
+            .section(&functions)
+            .section(&export_section)
+            .section(&code);
+        module.finish()
+    }
+
+    #[test]
+    fn composite_query_modes_test() -> Result<(), String> {
+        let data = "type Peer = service { peek : () -> (nat) composite_query };
+        service : {
+          // composite_query in comments is left alone
+          get : (record { composite_query : bool }) -> (nat) composite_query;
+          \"quoted name\" : () -> () composite_query;
+          size : () -> (nat) query;
+          put : (nat) -> ();
+          notify : () -> () oneway;
+        }";
+
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        let spec = &v["actor"]["Spec"]["Service"];
+        assert_eq!(spec["get"]["Func"]["mode"], "composite_query");
+        assert_eq!(
+            spec["get"]["Func"]["modes"],
+            serde_json::json!(["composite_query"])
+        );
+        assert_eq!(spec["quoted name"]["Func"]["mode"], "composite_query");
+        assert_eq!(spec["size"]["Func"]["modes"], serde_json::json!(["query"]));
+        assert_eq!(spec["put"]["Func"]["modes"], serde_json::json!([]));
+        assert_eq!(spec["put"]["Func"]["mode"], "update");
+        assert_eq!(spec["notify"]["Func"]["mode"], "oneway");
+        assert_eq!(
+            v["types"]["Peer"]["Service"]["peek"]["Func"]["mode"],
+            "composite_query"
+        );
+
+        let wasm = test_module(&[
+            "canister_query get",
+            "canister_composite_query quoted name",
+            "canister_query size",
+            "canister_update notify",
+            "canister_init",
+        ]);
+        let result = check_interface(&wasm, data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(
+            v["mode_mismatches"],
+            serde_json::json!([{ "declared": "composite_query", "exported": ["query"], "method": "get" }])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn composite_query_services_test() -> Result<(), String> {
+        let data = "type Peer = service { peek : () -> (nat) composite_query };
+        service : {
+          peek : () -> (nat) query;
+          scan : () -> (vec Peer) composite_query;
+        }";
+
+        // Composite queries are kept apart per service
+        let result = parse_candid(data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        let spec = &v["actor"]["Spec"]["Service"];
+        assert_eq!(spec["peek"]["Func"]["mode"], "query");
+        assert_eq!(spec["scan"]["Func"]["mode"], "composite_query");
+
+        let result = format_candid(data, None)?;
+        assert_eq!(
+            result,
+            "type Peer = service { peek : () -> (nat) composite_query };
+service : { peek : () -> (nat) query; scan : () -> (vec Peer) composite_query }
+"
+        );
+
+        let result = parse_candid_to_json_schema(data, false)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(v["methods"]["peek"]["mode"], "query");
+        assert_eq!(v["methods"]["scan"]["mode"], "composite_query");
+
+        let result = parse_candid_to_json_schema(data, true)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(
+            v["paths"]["/scan"]["post"]["tags"],
+            serde_json::json!(["composite_query"])
+        );
+
+        // The actor may be declared through a service type
+        let named = "type Peer = service { peek : () -> (nat) composite_query };
+        service : Peer";
+        let result = parse_candid_to_json_schema(named, false)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(v["methods"]["peek"]["mode"], "composite_query");
+
+        let new = data.replace("(nat) query", "(nat) composite_query");
+        let result = candid_diff(data, &new)?;
+        assert_eq!(
+            result,
+            r#"{"changes":[{"kind":"mode_changed","method":"peek","new":"composite_query","old":"query"}]}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_interface_test() -> Result<(), String> {
+        let data = "service : (nat) -> {
+          get : () -> (nat) query;
+          put : (nat) -> ();
+          remove : (nat) -> ();
+        }";
+
+        let wasm = test_module(&[
+            "canister_update get",
+            "canister_update put",
+            "canister_query extra",
+            "canister_query __get_candid_interface_tmp_hack",
+            "memory_size",
+        ]);
+        let result = check_interface(&wasm, data)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        assert_eq!(v["missing_exports"], serde_json::json!(["remove"]));
+        assert_eq!(
+            v["undeclared_exports"],
+            serde_json::json!(["canister_query extra"])
+        );
+        assert_eq!(v["mode_mismatches"][0]["method"], "get");
+        assert_eq!(v["init"], serde_json::json!({ "args": 1 }));
+
+        // canister_init without declared init args is not reported
+        let wasm = test_module(&["canister_init", "canister_query get", "canister_update put"]);
+        let result = check_interface(
+            &wasm,
+            "service : { get : () -> (nat) query; put : (nat) -> () }",
+        )?;
+        assert_eq!(
+            result,
+            r#"{"missing_exports":[],"undeclared_exports":[],"mode_mismatches":[],"init":null}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn message_cost_test() -> Result<(), String> {
+        use candid::{parser::value::IDLValue, Encode, IDLArgs};
+
+        let args = Encode!(&"hello").map_err(|x| format!("{x}"))?;
+        let result = candid_message_cost("greet", &args, None)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+
+        let size = 5 + args.len() as u64;
+        assert_eq!(v["size"], size);
+        assert_eq!(v["ingress"]["base_fee"], 1_200_000);
+        assert_eq!(v["ingress"]["transmission_fee"], 2_000 * size);
+        assert_eq!(v["ingress"]["total"], 1_200_000 + 2_000 * size);
+        assert_eq!(v["inter_canister"]["total"], 260_000 + 1_000 * size);
+        assert_eq!(v["ingress"]["within_limit"], true);
+
+        let result = candid_message_cost("greet", &args, Some(r#"{ "subnet_size": 34 }"#.into()))?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(v["ingress"]["base_fee"], 1_200_000 * 34 / 13);
+        assert_eq!(v["inter_canister"]["per_byte_fee"], 1_000 * 34 / 13);
+
+        let payload = vec![IDLValue::Nat8(0); 2 * 1024 * 1024];
+        let mut large = IDLArgs {
+            args: vec![IDLValue::Vec(payload)],
+        }
+        .to_bytes()
+        .map_err(|x| format!("{x}"))?;
+        let result = candid_message_cost("upload", &large, None)?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(v["ingress"]["within_limit"], false);
+        assert_eq!(v["inter_canister"]["within_limit"], false);
+
+        large.truncate(4);
+        assert!(candid_message_cost("upload", &large, None).is_err());
+
+        // Fees are multiplied before they are divided without overflowing
+        let subnet_size = u64::MAX / 1_000_000;
+        let config = format!(r#"{{ "subnet_size": {subnet_size} }}"#);
+        let result = candid_message_cost("greet", &args, Some(config))?;
+        let v: Value = serde_json::from_str(&result).map_err(|x| format!("{}", x))?;
+        assert_eq!(
+            v["ingress"]["base_fee"],
+            (1_200_000 * subnet_size as u128 / 13) as u64
+        );
+
+        let config = format!(r#"{{ "subnet_size": {} }}"#, u64::MAX);
+        assert!(candid_message_cost("greet", &args, Some(config)).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,209 @@
+//! Minimal valid replies of Candid methods, so a canister can be mocked from
+//! its interface file alone.
+//!
+//! Every value defaults to the smallest one of its type: zero, `false`, empty
+//! text and vectors, `null` for options, the variant case with the smallest
+//! value and the management canister for principals. Cases of the same size
+//! are ordered by label hash as on the wire, not as declared, so of those the
+//! one with the lowest index is picked. Recursive cases are only picked when
+//! no other case is smaller, so the default always terminates. Hints override
+//! parts of the reply with the JSON mapping of `target_json_schema`, anything
+//! not mentioned keeps its default.
+
+use std::{collections::BTreeSet, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use candid::{
+    parser::value::{IDLArgs, IDLField, IDLValue, VariantValue},
+    types::{Field, Label, Type},
+    Principal, TypeEnv,
+};
+use serde_json::Value;
+
+use crate::random::min_size;
+
+fn number<T: FromStr>(hint: &Value) -> Result<T, String> {
+    match hint {
+        Value::Number(n) => n.to_string().parse().ok(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("{hint} is not a valid number"))
+}
+
+fn text(hint: &Value) -> Result<&str, String> {
+    hint.as_str()
+        .ok_or_else(|| format!("{hint} is not a string"))
+}
+
+fn principal(hint: &Value) -> Result<Principal, String> {
+    Principal::from_text(text(hint)?).map_err(|x| format!("{x}"))
+}
+
+fn label_name(id: &Label) -> String {
+    match id {
+        Label::Named(name) => name.clone(),
+        Label::Id(n) | Label::Unnamed(n) => n.to_string(),
+    }
+}
+
+/// Case with the smallest value, the first in label hash order among equals
+fn smallest_case<'a>(env: &TypeEnv, fs: &'a [Field]) -> Option<&'a Field> {
+    fs.iter()
+        .enumerate()
+        .filter_map(|(i, f)| min_size(env, &mut BTreeSet::new(), &f.ty).map(|s| (s, i, f)))
+        .min_by_key(|(s, i, _)| (*s, *i))
+        .map(|(_, _, f)| f)
+}
+
+/// Default value of `ty`, with the parts given in `hint` replaced.
+pub fn value(env: &TypeEnv, ty: &Type, hint: Option<&Value>) -> Result<IDLValue, String> {
+    let hint = hint.filter(|x| !x.is_null());
+
+    let value = match (ty, hint) {
+        (Type::Var(id), _) => {
+            let ty = env.find_type(id).map_err(|x| format!("{x}"))?;
+            return value(env, ty, hint);
+        }
+        (Type::Null, _) => IDLValue::Null,
+        (Type::Reserved, _) => IDLValue::Reserved,
+        (Type::Bool, None) => IDLValue::Bool(false),
+        (Type::Bool, Some(h)) => {
+            IDLValue::Bool(h.as_bool().ok_or_else(|| format!("{h} is not a boolean"))?)
+        }
+        (Type::Nat, h) => IDLValue::Nat(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Int, h) => IDLValue::Int(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Nat8, h) => IDLValue::Nat8(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Nat16, h) => IDLValue::Nat16(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Nat32, h) => IDLValue::Nat32(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Nat64, h) => IDLValue::Nat64(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Int8, h) => IDLValue::Int8(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Int16, h) => IDLValue::Int16(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Int32, h) => IDLValue::Int32(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Int64, h) => IDLValue::Int64(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Float32, h) => IDLValue::Float32(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Float64, h) => IDLValue::Float64(h.map(number).transpose()?.unwrap_or_default()),
+        (Type::Text, h) => IDLValue::Text(h.map(text).transpose()?.unwrap_or_default().into()),
+        (Type::Principal, None) => IDLValue::Principal(Principal::management_canister()),
+        (Type::Principal, Some(h)) => IDLValue::Principal(principal(h)?),
+        (Type::Service(_), None) => IDLValue::Service(Principal::management_canister()),
+        (Type::Service(_), Some(h)) => IDLValue::Service(principal(h)?),
+        (Type::Func(_), None) => IDLValue::Func(Principal::management_canister(), String::new()),
+        (Type::Func(_), Some(h)) => match h.as_array().map(|x| x.as_slice()) {
+            Some([id, method]) => IDLValue::Func(principal(id)?, text(method)?.to_string()),
+            _ => return Err(format!("{h} is not a [principal, method] pair")),
+        },
+        (Type::Opt(_), None) => IDLValue::None,
+        (Type::Opt(t), Some(h)) => IDLValue::Opt(Box::new(value(env, t, Some(h))?)),
+        (Type::Vec(_), None) => IDLValue::Vec(Vec::new()),
+        (Type::Vec(t), Some(Value::String(data))) if **t == Type::Nat8 => {
+            let bytes = STANDARD
+                .decode(data)
+                .map_err(|_| format!("{data} is not valid base64"))?;
+            IDLValue::Vec(bytes.into_iter().map(IDLValue::Nat8).collect())
+        }
+        (Type::Vec(t), Some(h)) => IDLValue::Vec(
+            h.as_array()
+                .ok_or_else(|| format!("{h} is not an array"))?
+                .iter()
+                .map(|x| value(env, t, Some(x)))
+                .collect::<Result<_, _>>()?,
+        ),
+        (Type::Record(fs), h) => {
+            if min_size(env, &mut BTreeSet::new(), ty).is_none() {
+                return Err(format!("type {ty} has no finite values"));
+            }
+            if let Some(h) = h {
+                check_labels(fs, h)?;
+            }
+
+            let fields = fs
+                .iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    let hint = match h {
+                        Some(Value::Array(items)) => items.get(i),
+                        Some(h) => h.get(label_name(&f.id)),
+                        None => None,
+                    };
+                    Ok(IDLField {
+                        id: f.id.clone(),
+                        val: value(env, &f.ty, hint)?,
+                    })
+                })
+                .collect::<Result<_, String>>()?;
+            IDLValue::Record(fields)
+        }
+        (Type::Variant(fs), h) => {
+            let (case, hint) = match h {
+                None => (smallest_case(env, fs), None),
+                Some(Value::String(name)) => (fs.iter().find(|f| label_name(&f.id) == *name), None),
+                Some(Value::Object(map)) if map.len() == 1 => {
+                    let (name, hint) = map.iter().next().unwrap();
+                    (fs.iter().find(|f| label_name(&f.id) == *name), Some(hint))
+                }
+                Some(h) => return Err(format!("{h} is not a single variant case")),
+            };
+            let case = case.ok_or_else(|| match h {
+                Some(h) => format!("{h} is not a case of {ty}"),
+                None => format!("type {ty} has no finite values"),
+            })?;
+            let idx = fs.iter().position(|f| f.id == case.id).unwrap_or_default();
+
+            IDLValue::Variant(VariantValue(
+                Box::new(IDLField {
+                    id: case.id.clone(),
+                    val: value(env, &case.ty, hint)?,
+                }),
+                idx as u64,
+            ))
+        }
+        (Type::Empty | Type::Unknown | Type::Knot(_) | Type::Class(_, _), _) => {
+            return Err(format!("cannot build a value of type {ty}"));
+        }
+    };
+
+    Ok(value)
+}
+
+/// Hints for fields that do not exist are most likely typos
+fn check_labels(fs: &[Field], hint: &Value) -> Result<(), String> {
+    match hint {
+        Value::Object(map) => {
+            for name in map.keys() {
+                if !fs.iter().any(|f| label_name(&f.id) == *name) {
+                    return Err(format!("field {name} does not exist"));
+                }
+            }
+            Ok(())
+        }
+        Value::Array(items) if items.len() <= fs.len() => Ok(()),
+        _ => Err(format!(
+            "{hint} does not match a record with {} fields",
+            fs.len()
+        )),
+    }
+}
+
+/// Builds and encodes the reply of a method returning `types`. `hints` is an
+/// array with a hint for every returned value.
+pub fn reply(env: &TypeEnv, types: &[Type], hints: Option<&Value>) -> Result<Vec<u8>, String> {
+    let hints = match hints {
+        None | Some(Value::Null) => &[][..],
+        Some(Value::Array(items)) => items.as_slice(),
+        Some(h) => return Err(format!("{h} is not an array of reply hints")),
+    };
+    if hints.len() > types.len() {
+        return Err(format!("reply has only {} values", types.len()));
+    }
+
+    let args = types
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| value(env, ty, hints.get(i)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    IDLArgs { args }
+        .to_bytes_with_types(env, types)
+        .map_err(|x| format!("{x}"))
+}
@@ -0,0 +1,208 @@
+//! Method modes and the names of the exports that implement them.
+//!
+//! The candid parser does not know `composite_query` yet, so the annotation is
+//! replaced by `query` before parsing and the methods using it are tracked
+//! separately, for the actor's service and every `type Name = service { .. }`.
+//! Composite queries anywhere else, like on `func` types or services nested in
+//! other types, are read as plain queries.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{
+    parser::{
+        token::{Token, Tokenizer},
+        types::FuncMode,
+    },
+    types::{Function, Type},
+    TypeEnv,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Update,
+    Query,
+    CompositeQuery,
+    Oneway,
+}
+
+impl Mode {
+    pub fn of(func: &Function) -> Mode {
+        if func.modes.contains(&FuncMode::Query) {
+            Mode::Query
+        } else if func.modes.contains(&FuncMode::Oneway) {
+            Mode::Oneway
+        } else {
+            Mode::Update
+        }
+    }
+
+    /// Mode of the service method `name`, `composite` holds the composite
+    /// queries of its service.
+    pub fn of_method(name: &str, func: &Function, composite: &BTreeSet<String>) -> Mode {
+        match Mode::of(func) {
+            Mode::Query if composite.contains(name) => Mode::CompositeQuery,
+            mode => mode,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Update => "update",
+            Mode::Query => "query",
+            Mode::CompositeQuery => "composite_query",
+            Mode::Oneway => "oneway",
+        }
+    }
+
+    /// Annotations as written after the function type, updates have none.
+    pub fn annotations(self) -> &'static [&'static str] {
+        match self {
+            Mode::Update => &[],
+            Mode::Query => &["query"],
+            Mode::CompositeQuery => &["composite_query"],
+            Mode::Oneway => &["oneway"],
+        }
+    }
+
+    /// Prefix of the export a canister implements the method with, oneway
+    /// methods are exported as updates.
+    pub fn export_prefix(self) -> &'static str {
+        match self {
+            Mode::Update | Mode::Oneway => "canister_update",
+            Mode::Query => "canister_query",
+            Mode::CompositeQuery => "canister_composite_query",
+        }
+    }
+}
+
+const COMPOSITE_QUERY: &str = "composite_query";
+
+/// Services without composite queries
+pub static NO_COMPOSITE: BTreeSet<String> = BTreeSet::new();
+
+/// Composite query methods of the services declared in a file.
+#[derive(Default, Debug, PartialEq)]
+pub struct Composite {
+    /// Methods of the body of the actor's service
+    pub actor: BTreeSet<String>,
+    /// Methods of service types, by type name
+    pub types: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Composite {
+    pub fn of_type(&self, name: &str) -> &BTreeSet<String> {
+        self.types.get(name).unwrap_or(&NO_COMPOSITE)
+    }
+
+    /// Composite queries of the actor, also when its service is a named type.
+    pub fn of_actor(&self, env: &TypeEnv, actor: &Option<Type>) -> &BTreeSet<String> {
+        let mut ty = match actor {
+            Some(Type::Class(_, t)) => t.as_ref(),
+            Some(t) => t,
+            None => return &NO_COMPOSITE,
+        };
+
+        // Follow aliases to the declaration of the service
+        let mut name = None;
+        while let Type::Var(id) = ty {
+            name = Some(id);
+            match env.0.get(id) {
+                Some(t) => ty = t,
+                None => break,
+            }
+        }
+
+        match name {
+            Some(name) => self.of_type(name),
+            None => &self.actor,
+        }
+    }
+}
+
+/// Replaces every `composite_query` annotation in `src` by `query`, padded so
+/// positions in parser errors still match the source. Returns the new source
+/// and the composite queries of its services.
+pub fn strip_composite_queries(src: &str) -> (String, Composite) {
+    // Lexical errors are left to the parser, the rest of the file is kept as is
+    let tokens: Vec<(usize, Token, usize)> = Tokenizer::new(src).map_while(Result::ok).collect();
+    let mut out = String::with_capacity(src.len());
+    let mut last = 0;
+    let mut composite = Composite::default();
+
+    let mut depth = 0;
+    let mut parens = 0;
+    // Brace depth of every open service body, and the type it declares or
+    // `None` for the actor. Nested services are not tracked.
+    let mut services: Vec<(i32, Option<Option<String>>)> = Vec::new();
+    let mut after_service = false;
+    // Name of the type being declared at the top level
+    let mut type_name: Option<String> = None;
+    let mut method = String::new();
+
+    for (i, (start, token, end)) in tokens.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| &tokens[i].1);
+        let next = tokens.get(i + 1).map(|x| &x.1);
+        let service = services
+            .last()
+            .filter(|(d, _)| *d == depth && parens == 0)
+            .map(|(_, owner)| owner);
+
+        match token {
+            Token::Id(word) if word == COMPOSITE_QUERY && next != Some(&Token::Colon) => {
+                match service {
+                    Some(Some(None)) => {
+                        composite.actor.insert(method.clone());
+                    }
+                    Some(Some(Some(name))) => {
+                        composite
+                            .types
+                            .entry(name.clone())
+                            .or_default()
+                            .insert(method.clone());
+                    }
+                    _ => {}
+                }
+                out.push_str(&src[last..*start]);
+                out.push_str("query");
+                out.push_str(&" ".repeat(COMPOSITE_QUERY.len() - "query".len()));
+                last = *end;
+            }
+            Token::Id(word) | Token::Text(word)
+                if service.is_some() && next == Some(&Token::Colon) =>
+            {
+                method = word.clone();
+            }
+            Token::Id(word) if depth == 0 && prev == Some(&Token::Type) => {
+                type_name = Some(word.clone());
+            }
+            Token::Service => after_service = true,
+            Token::LBrace => {
+                depth += 1;
+                if after_service && parens == 0 {
+                    let owner = (depth == 1).then(|| type_name.clone());
+                    services.push((depth, owner));
+                    after_service = false;
+                }
+            }
+            Token::RBrace => {
+                if services.last().map(|x| x.0) == Some(depth) {
+                    services.pop();
+                }
+                depth -= 1;
+                after_service = false;
+            }
+            Token::Semi if parens == 0 => {
+                after_service = false;
+                if depth == 0 {
+                    type_name = None;
+                }
+            }
+            Token::LParen => parens += 1,
+            Token::RParen => parens -= 1,
+            _ => {}
+        }
+    }
+
+    out.push_str(&src[last..]);
+    (out, composite)
+}
@@ -0,0 +1,228 @@
+//! Principals: their textual form, their kinds and how self-authenticating
+//! principals and canister ids are derived.
+
+use serde::Serialize;
+use sha2::{Digest, Sha224};
+use wasm_bindgen::prelude::*;
+
+pub const ANONYMOUS: [u8; 1] = [4];
+pub const MANAGEMENT_CANISTER: [u8; 0] = [];
+
+/// Principals are at most 29 bytes long
+pub const MAX_LENGTH: usize = 29;
+
+const BASE32: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Management,
+    Opaque,
+    SelfAuthenticating,
+    Derived,
+    Anonymous,
+    Reserved,
+    Unknown,
+}
+
+/// Kind of the principal, given by its last byte.
+pub fn kind(id: &[u8]) -> Kind {
+    match id.last() {
+        None => Kind::Management,
+        Some(1) => Kind::Opaque,
+        Some(2) if id.len() == MAX_LENGTH => Kind::SelfAuthenticating,
+        Some(3) => Kind::Derived,
+        Some(4) if id.len() == 1 => Kind::Anonymous,
+        Some(0x7f) => Kind::Reserved,
+        _ => Kind::Unknown,
+    }
+}
+
+/// Self-authenticating principal of a DER public key.
+pub fn self_authenticating(public_key_der: &[u8]) -> Vec<u8> {
+    let mut id = Sha224::digest(public_key_der).to_vec();
+    id.push(2);
+    id
+}
+
+/// Principal bytes of the canister with id `n`.
+pub fn canister_id(n: u64) -> Vec<u8> {
+    let mut id = n.to_be_bytes().to_vec();
+    id.extend_from_slice(&[1, 1]);
+    id
+}
+
+/// Canister id as a number, none for principals that are not canister ids.
+pub fn canister_number(id: &[u8]) -> Option<u64> {
+    match id {
+        [n @ .., 1, 1] => Some(u64::from_be_bytes(n.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Lower case base32 without padding
+pub fn base32(data: &[u8]) -> String {
+    let mut text = String::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+    for byte in data {
+        bits = bits << 8 | *byte as u32;
+        count += 8;
+        while count >= 5 {
+            count -= 5;
+            text.push(BASE32[(bits >> count & 0x1f) as usize] as char);
+        }
+    }
+    if count > 0 {
+        text.push(BASE32[(bits << (5 - count) & 0x1f) as usize] as char);
+    }
+    text
+}
+
+/// Textual form: the CRC32 of the principal followed by the principal, in
+/// base32 and grouped by five characters.
+pub fn to_text(id: &[u8]) -> String {
+    let data = [&crc32fast::hash(id).to_be_bytes()[..], id].concat();
+
+    let chars: Vec<char> = base32(&data).chars().collect();
+    chars
+        .chunks(5)
+        .map(|c| c.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub fn from_text(text: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.chars().filter(|c| *c != '-') {
+        let c = c.to_ascii_lowercase() as u8;
+        let value = BASE32
+            .iter()
+            .position(|x| *x == c)
+            .ok_or_else(|| format!("Invalid character in principal {text}"))?;
+        bits = bits << 5 | value as u32;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+        }
+    }
+
+    if data.len() < 4 || data.len() > MAX_LENGTH + 4 {
+        return Err(format!("Invalid length of principal {text}"));
+    }
+    let id = data.split_off(4);
+    if data != crc32fast::hash(&id).to_be_bytes() {
+        return Err(format!("Invalid checksum of principal {text}"));
+    }
+    if to_text(&id) != text {
+        return Err(format!(
+            "{text} is not the canonical form of {}",
+            to_text(&id)
+        ));
+    }
+
+    Ok(id)
+}
+
+#[wasm_bindgen]
+pub fn principal_to_text(id: &[u8]) -> Result<String, String> {
+    if id.len() > MAX_LENGTH {
+        return Err(format!("Principal has more than {MAX_LENGTH} bytes"));
+    }
+    Ok(to_text(id))
+}
+
+#[wasm_bindgen]
+pub fn principal_from_text(text: &str) -> Result<Vec<u8>, String> {
+    from_text(text)
+}
+
+#[wasm_bindgen]
+pub fn principal_from_public_key(public_key_der: &[u8]) -> Vec<u8> {
+    self_authenticating(public_key_der)
+}
+
+/// Kind of the principal as JSON, like `"self_authenticating"`.
+#[wasm_bindgen]
+pub fn principal_kind(id: &[u8]) -> Result<String, String> {
+    serde_json::to_string(&kind(id)).map_err(|x| format!("{x}"))
+}
+
+/// Canister id `counter` places after the first canister id of a range.
+#[wasm_bindgen]
+pub fn principal_canister_id(range_start: &[u8], counter: u64) -> Result<Vec<u8>, String> {
+    let start = canister_number(range_start).ok_or("Range start is not a canister id")?;
+    let n = start
+        .checked_add(counter)
+        .ok_or("Canister id is out of range")?;
+    Ok(canister_id(n))
+}
+
+/// Number of a canister id, the inverse of `principal_canister_id`.
+#[wasm_bindgen]
+pub fn principal_canister_number(id: &[u8]) -> Result<u64, String> {
+    canister_number(id).ok_or_else(|| "Principal is not a canister id".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text() -> Result<(), String> {
+        assert_eq!(to_text(&MANAGEMENT_CANISTER), "aaaaa-aa");
+        assert_eq!(to_text(&ANONYMOUS), "2vxsx-fae");
+        assert_eq!(to_text(&canister_id(0)), "rwlgt-iiaaa-aaaaa-aaaaa-cai");
+        assert_eq!(to_text(&canister_id(1)), "rrkah-fqaaa-aaaaa-aaaaq-cai");
+
+        assert_eq!(from_text("rrkah-fqaaa-aaaaa-aaaaq-cai")?, canister_id(1));
+        assert_eq!(from_text("2vxsx-fae")?, ANONYMOUS);
+        assert_eq!(from_text("aaaaa-aa")?, MANAGEMENT_CANISTER);
+
+        assert!(from_text("rrkah-fqaaa-aaaaa-aaaaa-cai").is_err());
+        assert!(from_text("RRKAH-FQAAA-AAAAA-AAAAQ-CAI").is_err());
+        assert!(from_text("rrkahfqaaaaaaaaaaaaqcai").is_err());
+        assert!(from_text("rrkah-fqaaa-aaaaa-aaaaq-ca!").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn kinds() -> Result<(), String> {
+        let user = self_authenticating(b"some DER encoded public key");
+        assert_eq!(user.len(), MAX_LENGTH);
+        assert_eq!(kind(&user), Kind::SelfAuthenticating);
+
+        assert_eq!(kind(&ANONYMOUS), Kind::Anonymous);
+        assert_eq!(kind(&MANAGEMENT_CANISTER), Kind::Management);
+        assert_eq!(kind(&canister_id(5)), Kind::Opaque);
+        assert_eq!(kind(&[1, 2, 3, 0x7f]), Kind::Reserved);
+        assert_eq!(kind(&[1, 3]), Kind::Derived);
+        assert_eq!(kind(&[1, 2]), Kind::Unknown);
+        assert_eq!(principal_kind(&user)?, r#""self_authenticating""#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn canister_ids() -> Result<(), String> {
+        assert_eq!(canister_number(&canister_id(1 << 20)), Some(1 << 20));
+        assert_eq!(canister_number(&ANONYMOUS), None);
+
+        let start = canister_id(1 << 20);
+        assert_eq!(
+            principal_canister_id(&start, 3)?,
+            canister_id((1 << 20) + 3)
+        );
+        assert!(principal_canister_id(&canister_id(u64::MAX), 1).is_err());
+        assert!(principal_canister_id(&ANONYMOUS, 1).is_err());
+
+        assert_eq!(principal_canister_number(&canister_id(256))?, 256);
+        assert!(principal_canister_number(&ANONYMOUS).is_err());
+
+        Ok(())
+    }
+}
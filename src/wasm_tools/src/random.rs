@@ -0,0 +1,277 @@
+//! Random, well-typed Candid values for fuzzing canister methods.
+
+use std::collections::BTreeSet;
+
+use candid::{
+    parser::value::{IDLArgs, IDLField, IDLValue, VariantValue},
+    types::{Field, Type},
+    Int, Nat, Principal, TypeEnv,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Nesting level of opt, vec, record and variant values after which only
+    /// the smallest possible values are generated
+    pub depth: usize,
+    /// Upper bound on the number of values generated for a single argument
+    pub size: usize,
+    /// Maximum length of generated vectors, blobs and text
+    pub width: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            depth: 5,
+            size: 50,
+            width: 10,
+        }
+    }
+}
+
+/// Nesting past the configured depth at which a type is considered to have no
+/// finite values, e.g. `type T = record { next : T }`
+const MAX_EXTRA_DEPTH: usize = 64;
+
+const MAX_PRINCIPAL_LENGTH: u64 = 29;
+
+/// SplitMix64, small and deterministic so a seed always reproduces the same
+/// arguments
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n`, `n` must not be zero
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// Smallest number of values needed to build a value of `ty`, `None` when the
+/// type has no finite values.
+pub(crate) fn min_size(env: &TypeEnv, seen: &mut BTreeSet<String>, ty: &Type) -> Option<usize> {
+    match ty {
+        Type::Empty | Type::Unknown | Type::Knot(_) | Type::Class(_, _) => None,
+        Type::Var(id) => {
+            if !seen.insert(id.clone()) {
+                return None;
+            }
+            let res = env.find_type(id).ok().and_then(|t| min_size(env, seen, t));
+            seen.remove(id);
+            res
+        }
+        Type::Record(fs) => fs
+            .iter()
+            .map(|f| min_size(env, seen, &f.ty))
+            .sum::<Option<usize>>()
+            .map(|s| s + 1),
+        Type::Variant(fs) => fs
+            .iter()
+            .filter_map(|f| min_size(env, seen, &f.ty))
+            .min()
+            .map(|s| s + 1),
+        _ => Some(1),
+    }
+}
+
+struct GenState<'a> {
+    env: &'a TypeEnv,
+    config: &'a Config,
+    rng: &'a mut Rng,
+    depth: usize,
+    size: usize,
+}
+
+impl<'a> GenState<'a> {
+    fn exhausted(&self) -> bool {
+        self.depth >= self.config.depth || self.size == 0
+    }
+
+    fn len(&mut self) -> usize {
+        if self.exhausted() {
+            return 0;
+        }
+        let max = self.config.width.min(self.size);
+        self.rng.below(max as u64 + 1) as usize
+    }
+
+    /// Integers are biased towards boundary values, which is where canisters
+    /// usually trap
+    fn edge<T: Copy>(&mut self, edges: [T; 3], random: T) -> T {
+        match self.rng.below(8) {
+            0..=2 => edges[self.rng.below(3) as usize],
+            _ => random,
+        }
+    }
+
+    fn text(&mut self) -> String {
+        let len = self.len();
+        (0..len)
+            .map(|_| match self.rng.below(16) {
+                0 => char::from_u32(0x00a0 + self.rng.below(0xd000) as u32).unwrap_or('?'),
+                _ => (0x20 + self.rng.below(0x5f) as u8) as char,
+            })
+            .collect()
+    }
+
+    fn principal(&mut self) -> Principal {
+        let len = self.rng.below(MAX_PRINCIPAL_LENGTH + 1) as usize;
+        let bytes: Vec<u8> = (0..len).map(|_| self.rng.next_u64() as u8).collect();
+        Principal::from_slice(&bytes)
+    }
+
+    fn any(&mut self, ty: &Type) -> Result<IDLValue, String> {
+        if self.depth > self.config.depth + MAX_EXTRA_DEPTH {
+            return Err(format!("type {ty} has no finite values"));
+        }
+        self.size = self.size.saturating_sub(1);
+
+        let r = self.rng.next_u64();
+        let value = match ty {
+            Type::Var(id) => {
+                let ty = self.env.find_type(id).map_err(|x| format!("{x}"))?;
+                return self.any(ty);
+            }
+            Type::Null => IDLValue::Null,
+            Type::Reserved => IDLValue::Reserved,
+            Type::Bool => IDLValue::Bool(r & 1 == 1),
+            Type::Nat => {
+                let wide = (r as u128) << 64 | self.rng.next_u64() as u128;
+                IDLValue::Nat(Nat::from(self.edge([0, 1, u128::MAX], wide >> (r % 128))))
+            }
+            Type::Int => {
+                let wide = ((r as u128) << 64 | self.rng.next_u64() as u128) as i128;
+                IDLValue::Int(Int::from(
+                    self.edge([0, i128::MIN, i128::MAX], wide >> (r % 128)),
+                ))
+            }
+            Type::Nat8 => IDLValue::Nat8(self.edge([0, 1, u8::MAX], r as u8)),
+            Type::Nat16 => IDLValue::Nat16(self.edge([0, 1, u16::MAX], r as u16)),
+            Type::Nat32 => IDLValue::Nat32(self.edge([0, 1, u32::MAX], r as u32)),
+            Type::Nat64 => IDLValue::Nat64(self.edge([0, 1, u64::MAX], r)),
+            Type::Int8 => IDLValue::Int8(self.edge([0, i8::MIN, i8::MAX], r as i8)),
+            Type::Int16 => IDLValue::Int16(self.edge([0, i16::MIN, i16::MAX], r as i16)),
+            Type::Int32 => IDLValue::Int32(self.edge([0, i32::MIN, i32::MAX], r as i32)),
+            Type::Int64 => IDLValue::Int64(self.edge([0, i64::MIN, i64::MAX], r as i64)),
+            Type::Float32 => IDLValue::Float32(f32::from_bits(r as u32)),
+            Type::Float64 => IDLValue::Float64(f64::from_bits(r)),
+            Type::Text => IDLValue::Text(self.text()),
+            Type::Principal => IDLValue::Principal(self.principal()),
+            Type::Opt(t) => {
+                if self.exhausted() || r & 1 == 0 {
+                    IDLValue::None
+                } else {
+                    self.depth += 1;
+                    let v = self.any(t);
+                    self.depth -= 1;
+                    IDLValue::Opt(Box::new(v?))
+                }
+            }
+            Type::Vec(t) => {
+                let len = self.len();
+                self.depth += 1;
+                let items = (0..len).map(|_| self.any(t)).collect::<Result<Vec<_>, _>>();
+                self.depth -= 1;
+                IDLValue::Vec(items?)
+            }
+            Type::Record(fs) => {
+                self.depth += 1;
+                let fields = fs
+                    .iter()
+                    .map(|Field { id, ty }| {
+                        Ok(IDLField {
+                            id: id.clone(),
+                            val: self.any(ty)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>();
+                self.depth -= 1;
+                IDLValue::Record(fields?)
+            }
+            Type::Variant(fs) => {
+                let sizes: Vec<_> = fs
+                    .iter()
+                    .map(|f| min_size(self.env, &mut BTreeSet::new(), &f.ty))
+                    .collect();
+
+                let idx = if self.exhausted() {
+                    // Pick the cheapest case so recursive variants terminate
+                    sizes
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, s)| s.map(|s| (s, i)))
+                        .min()
+                        .map(|(_, i)| i)
+                } else {
+                    let inhabited: Vec<_> = (0..fs.len()).filter(|i| sizes[*i].is_some()).collect();
+                    match inhabited.len() {
+                        0 => None,
+                        n => Some(inhabited[self.rng.below(n as u64) as usize]),
+                    }
+                }
+                .ok_or_else(|| format!("type {ty} has no finite values"))?;
+
+                let Field { id, ty } = &fs[idx];
+                self.depth += 1;
+                let val = self.any(ty);
+                self.depth -= 1;
+                IDLValue::Variant(VariantValue(
+                    Box::new(IDLField {
+                        id: id.clone(),
+                        val: val?,
+                    }),
+                    idx as u64,
+                ))
+            }
+            Type::Service(_) => IDLValue::Service(self.principal()),
+            Type::Func(_) => {
+                let method = self.text();
+                IDLValue::Func(self.principal(), method)
+            }
+            Type::Empty | Type::Unknown | Type::Knot(_) | Type::Class(_, _) => {
+                return Err(format!("cannot generate a value of type {ty}"));
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+/// Generates random arguments for `types` and encodes them.
+pub fn random_args(
+    env: &TypeEnv,
+    types: &[Type],
+    seed: u64,
+    config: &Config,
+) -> Result<Vec<u8>, String> {
+    let mut rng = Rng::new(seed);
+
+    let mut args = Vec::new();
+    for ty in types {
+        let mut state = GenState {
+            env,
+            config,
+            rng: &mut rng,
+            depth: 0,
+            size: config.size,
+        };
+        args.push(state.any(ty)?);
+    }
+
+    IDLArgs { args }
+        .to_bytes_with_types(env, types)
+        .map_err(|x| format!("{x}"))
+}
@@ -0,0 +1,133 @@
+//! Representation-independent hashing of request contents, which gives the
+//! request id of calls, queries and `read_state` requests.
+
+use ciborium::value::Value;
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+use crate::hash_tree::decode_cbor;
+
+fn leb128(mut n: u128) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn sleb128(mut n: i128) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Hash of a value: blobs and text hash their bytes, numbers their (signed)
+/// LEB128 encoding, arrays the concatenated hashes of their items and maps the
+/// sorted concatenated hashes of their key and value pairs.
+pub fn hash_of(value: &Value) -> Result<[u8; 32], String> {
+    let hash = match value {
+        Value::Bytes(b) => Sha256::digest(b),
+        Value::Text(t) => Sha256::digest(t.as_bytes()),
+        Value::Integer(i) => {
+            let i = i128::from(*i);
+            match u128::try_from(i) {
+                Ok(n) => Sha256::digest(leb128(n)),
+                Err(_) => Sha256::digest(sleb128(i)),
+            }
+        }
+        Value::Array(items) => {
+            let mut hash = Sha256::new();
+            for item in items {
+                hash.update(hash_of(item)?);
+            }
+            hash.finalize()
+        }
+        Value::Map(fields) => {
+            let mut pairs = fields
+                .iter()
+                .map(|(k, v)| Ok([hash_of(k)?, hash_of(v)?].concat()))
+                .collect::<Result<Vec<_>, String>>()?;
+            pairs.sort();
+            Sha256::digest(pairs.concat())
+        }
+        Value::Tag(_, value) => return hash_of(value),
+        _ => return Err(format!("Cannot hash {value:?}")),
+    };
+
+    Ok(hash.into())
+}
+
+/// Request id of the CBOR `content` of a request.
+#[wasm_bindgen]
+pub fn request_id(content: &[u8]) -> Result<Vec<u8>, String> {
+    let content = decode_cbor(content)?;
+    if content.as_map().is_none() {
+        return Err("Request content is not a map".to_string());
+    }
+
+    Ok(hash_of(&content)?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::encode_cbor;
+    use hex_literal::hex;
+
+    fn field(name: &str, value: Value) -> (Value, Value) {
+        (Value::Text(name.into()), value)
+    }
+
+    #[test]
+    fn spec_example() -> Result<(), String> {
+        let content = Value::Map(vec![
+            field("request_type", "call".into()),
+            field(
+                "canister_id",
+                Value::Bytes(hex!("00000000000004D2").to_vec()),
+            ),
+            field("method_name", "hello".into()),
+            field("arg", Value::Bytes(b"DIDL\x00\xFD*".to_vec())),
+        ]);
+
+        assert_eq!(
+            request_id(&encode_cbor(content)?)?,
+            hex!("8781291c347db32a9d8c10eb62b710fce5a93be676474c42babc74c51858f94b")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encodings() -> Result<(), String> {
+        assert_eq!(leb128(624485), hex!("e58e26"));
+        assert_eq!(sleb128(-123456), hex!("c0bb78"));
+        assert_eq!(sleb128(-1), hex!("7f"));
+
+        // Field order does not matter
+        let a = Value::Map(vec![field("a", 1.into()), field("b", (-2).into())]);
+        let b = Value::Map(vec![field("b", (-2).into()), field("a", 1.into())]);
+        assert_eq!(hash_of(&a)?, hash_of(&b)?);
+
+        let paths = Value::Array(vec![Value::Array(vec![Value::Bytes(b"time".to_vec())])]);
+        let inner = Sha256::digest(Sha256::digest(b"time"));
+        assert_eq!(hash_of(&paths)?, <[u8; 32]>::from(Sha256::digest(inner)));
+
+        assert!(hash_of(&Value::Bool(true)).is_err());
+        assert!(request_id(&encode_cbor(paths)?).is_err());
+
+        Ok(())
+    }
+}
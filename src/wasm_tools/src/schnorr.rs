@@ -0,0 +1,253 @@
+//! Mock threshold Schnorr of the management canister.
+//!
+//! `bip340secp256k1` keys are derived like the ECDSA ones, `ed25519` keys
+//! with the HKDF based scheme the IC uses for Ed25519. Both start from a
+//! fixed master key per key name and a zero chain code.
+
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use ed25519_dalek::{
+    hazmat::{raw_sign, ExpandedSecretKey},
+    SecretKey, VerifyingKey,
+};
+use hkdf::Hkdf;
+use k256::{elliptic_curve::sec1::ToEncodedPoint, NonZeroScalar};
+use sha2::{Digest, Sha512};
+use wasm_bindgen::prelude::*;
+
+use crate::ecdsa;
+use crate::hash_tree::{domain_sep, sha256};
+
+enum Algorithm {
+    Bip340Secp256k1,
+    Ed25519,
+}
+
+impl Algorithm {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "bip340secp256k1" => Ok(Self::Bip340Secp256k1),
+            "ed25519" => Ok(Self::Ed25519),
+            _ => Err(format!("Unsupported Schnorr algorithm {name}")),
+        }
+    }
+}
+
+fn bip340_master_key(key_name: &str) -> Result<k256::ecdsa::SigningKey, String> {
+    let secret = sha256(&[
+        &domain_sep("lightic-bip340-master-key"),
+        key_name.as_bytes(),
+    ]);
+    k256::ecdsa::SigningKey::from_slice(&secret).map_err(|x| format!("{x}"))
+}
+
+fn ed25519_master_key(key_name: &str) -> ExpandedSecretKey {
+    let secret: SecretKey = sha256(&[
+        &domain_sep("lightic-ed25519-master-key"),
+        key_name.as_bytes(),
+    ]);
+    ExpandedSecretKey::from(&secret)
+}
+
+fn ed25519_ckd(
+    index: &[u8],
+    point: &EdwardsPoint,
+    chain_code: &[u8; 32],
+) -> ([u8; 32], Scalar, EdwardsPoint) {
+    let ikm = [&point.compress().0[..], index].concat();
+    let mut okm = [0; 96];
+    Hkdf::<Sha512>::new(Some(chain_code), &ikm)
+        .expand(b"Ed25519", &mut okm)
+        .unwrap();
+
+    // The offset is big endian, dalek scalars are little endian
+    let mut offset: [u8; 64] = okm[..64].try_into().unwrap();
+    offset.reverse();
+    let offset = Scalar::from_bytes_mod_order_wide(&offset);
+
+    (
+        okm[64..].try_into().unwrap(),
+        offset,
+        point + EdwardsPoint::mul_base(&offset),
+    )
+}
+
+/// Derived public key, chain code and the sum of the tweaks.
+fn ed25519_derive(master: &EdwardsPoint, path: &[Vec<u8>]) -> (EdwardsPoint, [u8; 32], Scalar) {
+    let mut point = *master;
+    let mut chain_code = [0; 32];
+    let mut sum = Scalar::ZERO;
+
+    for index in path {
+        let (next_chain_code, offset, next) = ed25519_ckd(index, &point, &chain_code);
+        point = next;
+        chain_code = next_chain_code;
+        sum += offset;
+    }
+
+    (point, chain_code, sum)
+}
+
+/// Public key followed by the chain code. BIP340 keys are SEC1 compressed,
+/// Ed25519 keys are 32 bytes long.
+#[wasm_bindgen]
+pub fn schnorr_public_key(
+    algorithm: &str,
+    key_name: &str,
+    canister_id: &[u8],
+    derivation_path: &[u8],
+) -> Result<Vec<u8>, String> {
+    let path = ecdsa::full_path(canister_id, derivation_path)?;
+
+    let (mut result, chain_code) = match Algorithm::from_name(algorithm)? {
+        Algorithm::Bip340Secp256k1 => {
+            let master = bip340_master_key(key_name)?;
+            let key = ecdsa::derive(master.verifying_key(), &path);
+            (
+                key.public_key.to_encoded_point(true).as_bytes().to_vec(),
+                key.chain_code,
+            )
+        }
+        Algorithm::Ed25519 => {
+            let master = ed25519_master_key(key_name);
+            let (point, chain_code, _) =
+                ed25519_derive(&EdwardsPoint::mul_base(&master.scalar), &path);
+            (point.compress().0.to_vec(), chain_code)
+        }
+    };
+
+    result.extend_from_slice(&chain_code);
+    Ok(result)
+}
+
+/// 64 byte signature of `message`, BIP340 for secp256k1 keys and pure
+/// Ed25519 for Ed25519 keys.
+#[wasm_bindgen]
+pub fn sign_with_schnorr(
+    algorithm: &str,
+    key_name: &str,
+    canister_id: &[u8],
+    derivation_path: &[u8],
+    message: &[u8],
+) -> Result<Vec<u8>, String> {
+    let path = ecdsa::full_path(canister_id, derivation_path)?;
+
+    match Algorithm::from_name(algorithm)? {
+        Algorithm::Bip340Secp256k1 => {
+            let master = bip340_master_key(key_name)?;
+            let key = ecdsa::derive(master.verifying_key(), &path);
+            let secret: Option<NonZeroScalar> =
+                NonZeroScalar::new(*master.as_nonzero_scalar().as_ref() + key.offset).into();
+            let secret = secret.ok_or("Derived key is zero")?;
+
+            // Keys with an odd y are negated by the signing key, as BIP340
+            // only uses the x coordinate
+            let signing_key = k256::schnorr::SigningKey::from_bytes(&secret.to_bytes())
+                .map_err(|x| format!("{x}"))?;
+            let signature = signing_key
+                .sign_raw(message, &sha256(&[message]))
+                .map_err(|x| format!("{x}"))?;
+            Ok(signature.to_bytes().to_vec())
+        }
+        Algorithm::Ed25519 => {
+            let master = ed25519_master_key(key_name);
+            let (point, _, sum) = ed25519_derive(&EdwardsPoint::mul_base(&master.scalar), &path);
+
+            let mut hash_prefix = Sha512::new();
+            hash_prefix.update(master.hash_prefix);
+            hash_prefix.update(sum.to_bytes());
+            let key = ExpandedSecretKey {
+                scalar: master.scalar + sum,
+                hash_prefix: hash_prefix.finalize()[..32].try_into().unwrap(),
+            };
+
+            let public_key =
+                VerifyingKey::from_bytes(&point.compress().0).map_err(|x| format!("{x}"))?;
+            Ok(raw_sign::<Sha512>(&key, message, &public_key)
+                .to_bytes()
+                .to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecdsa::cbor_path;
+
+    const CANISTER_ID: [u8; 10] = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+
+    #[test]
+    fn bip340_signatures_verify() -> Result<(), String> {
+        let derivation_path = cbor_path(&[b"taproot", &[0, 0, 0, 1]]);
+        let key = schnorr_public_key(
+            "bip340secp256k1",
+            "dfx_test_key",
+            &CANISTER_ID,
+            &derivation_path,
+        )?;
+        assert_eq!(key.len(), 65);
+
+        for message in [&b"message"[..], &[], &[7; 100]] {
+            let signature = sign_with_schnorr(
+                "bip340secp256k1",
+                "dfx_test_key",
+                &CANISTER_ID,
+                &derivation_path,
+                message,
+            )?;
+            let signature =
+                k256::schnorr::Signature::try_from(&signature[..]).map_err(|x| format!("{x}"))?;
+
+            let public_key =
+                k256::schnorr::VerifyingKey::from_bytes(&key[1..33]).map_err(|x| format!("{x}"))?;
+            assert!(public_key.verify_raw(message, &signature).is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ed25519_signatures_verify() -> Result<(), String> {
+        let derivation_path = cbor_path(&[b"solana"]);
+        let key = schnorr_public_key("ed25519", "dfx_test_key", &CANISTER_ID, &derivation_path)?;
+        assert_eq!(key.len(), 64);
+        assert_eq!(
+            key,
+            schnorr_public_key("ed25519", "dfx_test_key", &CANISTER_ID, &derivation_path)?
+        );
+
+        let signature = sign_with_schnorr(
+            "ed25519",
+            "dfx_test_key",
+            &CANISTER_ID,
+            &derivation_path,
+            b"message",
+        )?;
+        let signature =
+            ed25519_dalek::Signature::from_slice(&signature).map_err(|x| format!("{x}"))?;
+        let public_key =
+            VerifyingKey::from_bytes(key[..32].try_into().unwrap()).map_err(|x| format!("{x}"))?;
+        assert!(public_key.verify_strict(b"message", &signature).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_depend_on_algorithm_and_path() -> Result<(), String> {
+        let empty = cbor_path(&[]);
+        let bip340 = schnorr_public_key("bip340secp256k1", "dfx_test_key", &CANISTER_ID, &empty)?;
+        let ecdsa = ecdsa::ecdsa_public_key("dfx_test_key", &CANISTER_ID, &empty)?;
+        assert_ne!(bip340, ecdsa);
+
+        let ed25519 = schnorr_public_key("ed25519", "dfx_test_key", &CANISTER_ID, &empty)?;
+        assert_ne!(
+            ed25519,
+            schnorr_public_key("ed25519", "dfx_test_key", &CANISTER_ID, &cbor_path(&[b"a"]))?
+        );
+        assert_ne!(
+            ed25519,
+            schnorr_public_key("ed25519", "test_key_1", &CANISTER_ID, &empty)?
+        );
+
+        assert!(schnorr_public_key("rsa", "dfx_test_key", &CANISTER_ID, &empty).is_err());
+        Ok(())
+    }
+}
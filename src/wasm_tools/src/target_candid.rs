@@ -0,0 +1,275 @@
+//! Canonical Candid text for an interface.
+//!
+//! Adapted from `candid::bindings::candid`. Type definitions are printed in
+//! name order, aliases of other named types (`type A = B`) are replaced by the
+//! type they name, and doc comments can be printed back in front of the
+//! declarations they belong to. Types declared on their own are never merged,
+//! even when their definitions are identical. Composite queries of the actor
+//! and of service types keep their annotation.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{
+    bindings::candid::pp_label,
+    parser::types::FuncMode,
+    pretty::{concat, enclose, enclose_space, kwd, lines, str, INDENT_SPACE, LINE_WIDTH},
+    types::{Field, Function, Type},
+    TypeEnv,
+};
+use pretty::RcDoc;
+use serde::Deserialize;
+
+use crate::{
+    doc_comments::{DocNode, Docs},
+    modes::{Composite, NO_COMPOSITE},
+    target_json::is_tuple,
+};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Print doc comments of the source in front of their declarations
+    pub comments: bool,
+}
+
+static KEYWORDS: [&str; 29] = [
+    "import",
+    "service",
+    "func",
+    "type",
+    "opt",
+    "vec",
+    "record",
+    "variant",
+    "blob",
+    "principal",
+    "nat",
+    "nat8",
+    "nat16",
+    "nat32",
+    "nat64",
+    "int",
+    "int8",
+    "int16",
+    "int32",
+    "int64",
+    "float32",
+    "float64",
+    "bool",
+    "text",
+    "null",
+    "reserved",
+    "empty",
+    "oneway",
+    "query",
+];
+
+fn ident_string(id: &str) -> String {
+    let valid = id.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid && !KEYWORDS.contains(&id) {
+        id.to_string()
+    } else {
+        format!("\"{}\"", id.escape_debug())
+    }
+}
+
+fn pp_doc(node: Option<&DocNode>) -> RcDoc<'_> {
+    match node.and_then(|n| n.doc.as_ref()) {
+        None => RcDoc::nil(),
+        Some(doc) => lines(doc.lines().map(|line| match line {
+            "" => str("//"),
+            line => RcDoc::text(format!("// {line}")),
+        })),
+    }
+}
+
+fn child<'a>(node: Option<&'a DocNode>, name: &str) -> Option<&'a DocNode> {
+    node.and_then(|n| n.fields.get(name))
+}
+
+fn pp_ty<'a>(ty: &'a Type, node: Option<&'a DocNode>) -> RcDoc<'a> {
+    use Type::*;
+    match ty {
+        Null => str("null"),
+        Bool => str("bool"),
+        Nat => str("nat"),
+        Int => str("int"),
+        Nat8 => str("nat8"),
+        Nat16 => str("nat16"),
+        Nat32 => str("nat32"),
+        Nat64 => str("nat64"),
+        Int8 => str("int8"),
+        Int16 => str("int16"),
+        Int32 => str("int32"),
+        Int64 => str("int64"),
+        Float32 => str("float32"),
+        Float64 => str("float64"),
+        Text => str("text"),
+        Reserved => str("reserved"),
+        Empty => str("empty"),
+        Principal => str("principal"),
+        Var(s) => str(s),
+        Opt(t) => kwd("opt").append(pp_ty(t, node)),
+        Vec(t) if **t == Nat8 => str("blob"),
+        Vec(t) => kwd("vec").append(pp_ty(t, node)),
+        Record(fs) if is_tuple(ty) => {
+            let tuple = concat(fs.iter().map(|f| pp_ty(&f.ty, None)), ";");
+            kwd("record").append(enclose_space("{", tuple, "}"))
+        }
+        Record(fs) => kwd("record").append(pp_fields(fs, false, node)),
+        Variant(fs) => kwd("variant").append(pp_fields(fs, true, node)),
+        Func(func) => kwd("func").append(pp_function(func, false)),
+        Service(serv) => {
+            kwd("service").append(pp_service(serv, node.map(|n| &n.fields), &NO_COMPOSITE))
+        }
+        Class(args, t) => pp_args(args)
+            .append(" ->")
+            .append(RcDoc::space())
+            .append(pp_ty(t, node)),
+        Knot(id) => RcDoc::text(format!("{id}")),
+        Unknown => str("unknown"),
+    }
+}
+
+fn pp_fields<'a>(fs: &'a [Field], is_variant: bool, node: Option<&'a DocNode>) -> RcDoc<'a> {
+    let fields = fs.iter().map(move |f| {
+        let node = child(node, &f.id.to_string());
+        let ty = if is_variant && f.ty == Type::Null {
+            RcDoc::nil()
+        } else {
+            kwd(" :").append(pp_ty(&f.ty, node))
+        };
+        pp_doc(node).append(pp_label(&f.id)).append(ty)
+    });
+    enclose_space("{", concat(fields, ";"), "}")
+}
+
+fn pp_function(func: &Function, composite: bool) -> RcDoc<'_> {
+    let modes = RcDoc::concat(func.modes.iter().map(|m| {
+        RcDoc::space().append(match m {
+            FuncMode::Query if composite => "composite_query",
+            FuncMode::Query => "query",
+            FuncMode::Oneway => "oneway",
+        })
+    }));
+
+    pp_args(&func.args)
+        .append(" ->")
+        .append(RcDoc::space())
+        .append(pp_args(&func.rets).append(modes))
+        .nest(INDENT_SPACE)
+}
+
+fn pp_args(args: &[Type]) -> RcDoc<'_> {
+    let doc = concat(args.iter().map(|t| pp_ty(t, None)), ",");
+    enclose("(", doc, ")")
+}
+
+fn pp_service<'a>(
+    serv: &'a [(String, Type)],
+    docs: Option<&'a BTreeMap<String, DocNode>>,
+    composite: &'a BTreeSet<String>,
+) -> RcDoc<'a> {
+    let methods = serv.iter().map(move |(id, ty)| {
+        let func = match ty {
+            Type::Func(f) => pp_function(f, composite.contains(id)),
+            _ => pp_ty(ty, None),
+        };
+        pp_doc(docs.and_then(|d| d.get(id)))
+            .append(RcDoc::text(ident_string(id)))
+            .append(kwd(" :"))
+            .append(func)
+    });
+    enclose_space("{", concat(methods, ";"), "}")
+}
+
+fn pp_defs<'a>(env: &'a TypeEnv, docs: &'a Docs, composite: &'a Composite) -> RcDoc<'a> {
+    lines(env.0.iter().map(|(id, ty)| {
+        let node = docs.types.get(id);
+        let ty = match ty {
+            Type::Service(serv) => kwd("service").append(pp_service(
+                serv,
+                node.map(|n| &n.fields),
+                composite.of_type(id),
+            )),
+            ty => pp_ty(ty, node),
+        };
+        pp_doc(node)
+            .append(kwd("type"))
+            .append(kwd(id))
+            .append(kwd("="))
+            .append(ty)
+            .append(";")
+    }))
+}
+
+/// Name every alias of another named type should be printed as.
+fn aliases(env: &TypeEnv) -> BTreeMap<String, String> {
+    let tau: BTreeMap<&String, &String> = env
+        .0
+        .iter()
+        .filter_map(|(name, ty)| match ty {
+            Type::Var(id) => Some((name, id)),
+            _ => None,
+        })
+        .collect();
+
+    // Follow chains of aliases, the type checker already rejected cycles
+    tau.keys()
+        .map(|name| {
+            let mut target = tau[name];
+            while let Some(next) = tau.get(target) {
+                target = next;
+            }
+            ((*name).clone(), target.clone())
+        })
+        .collect()
+}
+
+fn dedup(env: TypeEnv, actor: Option<Type>) -> (TypeEnv, Option<Type>) {
+    let tau = aliases(&env);
+
+    let env = TypeEnv(
+        env.0
+            .into_iter()
+            .filter(|(name, _)| !tau.contains_key(name))
+            .map(|(name, ty)| (name, ty.subst(&tau)))
+            .collect(),
+    );
+    (env, actor.map(|t| t.subst(&tau)))
+}
+
+pub fn compile(env: TypeEnv, actor: Option<Type>, docs: &Docs, composite: &Composite) -> String {
+    let actor_composite = composite.of_actor(&env, &actor);
+    let (env, actor) = dedup(env, actor);
+
+    let defs = pp_defs(&env, docs, composite);
+    let doc = match &actor {
+        None => defs,
+        Some(actor) => {
+            let actor = match actor {
+                Type::Service(serv) => pp_service(serv, Some(&docs.methods), actor_composite),
+                Type::Class(args, t) => {
+                    let serv = match t.as_ref() {
+                        Type::Service(serv) => {
+                            pp_service(serv, Some(&docs.methods), actor_composite)
+                        }
+                        t => pp_ty(t, None),
+                    };
+                    pp_args(args)
+                        .append(" ->")
+                        .append(RcDoc::space())
+                        .append(serv)
+                }
+                t => pp_ty(t, None),
+            };
+            defs.append(kwd("service :"))
+                .append(actor)
+                .append(RcDoc::hardline())
+        }
+    };
+
+    doc.pretty(LINE_WIDTH).to_string()
+}
@@ -1,6 +1,11 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    doc_comments::{DocNode, Docs},
+    modes::{Composite, Mode, NO_COMPOSITE},
+};
 use candid::{
-    parser::types::FuncMode,
-    types::{Field, Type},
+    types::{Field, Function, Label, Type},
     TypeEnv,
 };
 
@@ -22,25 +27,47 @@ pub(crate) fn is_tuple(t: &Type) -> bool {
     }
 }
 
-pub fn print_modes(mo: &Vec<FuncMode>) -> String {
+pub fn print_modes(mode: Mode) -> String {
     let mut str = String::new();
     str.push('[');
 
     let mut first = true;
-    for p in mo {
+    for p in mode.annotations() {
         if !first {
             str.push(',');
         }
         first = false;
 
-        str.push_str(&format!("\"{p:?}\""));
+        str.push_str(&format!("\"{p}\""));
     }
 
     str.push(']');
     str
 }
 
-pub fn print_array(ty: &Vec<Type>) -> String {
+/// Docs of the fields, cases or methods of a type, keyed by label.
+type FieldDocs<'a> = Option<&'a BTreeMap<String, DocNode>>;
+
+/// `s` as a quoted JSON string, labels and docs may hold quotes and escapes.
+fn json_string(s: &str) -> String {
+    serde_json::Value::from(s).to_string()
+}
+
+/// Adds `doc` to a printed type. Types printed as plain strings or `null` are
+/// wrapped as `{ "Prim": ... }` so they can carry it.
+pub fn with_doc(printed: String, node: Option<&DocNode>) -> String {
+    let Some(doc) = node.and_then(|x| x.doc.as_ref()) else {
+        return printed;
+    };
+    let doc = json_string(doc);
+
+    match printed.strip_prefix('{') {
+        Some(rest) => format!("{{ \"doc\": {doc},{rest}"),
+        None => format!("{{ \"Prim\": {printed}, \"doc\": {doc} }}"),
+    }
+}
+
+pub fn print_array(ty: &Vec<Type>, recs: &BTreeSet<String>) -> String {
     let mut str = String::new();
 
     str.push('[');
@@ -52,7 +79,7 @@ pub fn print_array(ty: &Vec<Type>) -> String {
         }
         first = false;
 
-        str.push_str(&print_type(p));
+        str.push_str(&print_type(p, recs, &NO_COMPOSITE, None));
     }
 
     str.push(']');
@@ -60,7 +87,14 @@ pub fn print_array(ty: &Vec<Type>) -> String {
     str
 }
 
-pub fn print_type(ty: &Type) -> String {
+/// `docs` holds the docs of the labels of `ty`, or of the type it wraps, and
+/// `composite` the composite queries of `ty` when it is a declared service.
+pub fn print_type(
+    ty: &Type,
+    recs: &BTreeSet<String>,
+    composite: &BTreeSet<String>,
+    docs: FieldDocs,
+) -> String {
     let mut str = String::new();
 
     match ty {
@@ -82,60 +116,81 @@ pub fn print_type(ty: &Type) -> String {
         | Type::Principal => str.push_str(&format!("\"{ty}\"")),
 
         Type::Opt(s) => {
-            str.push_str(&format!("{{ \"Opt\": {} }}", print_type(s)));
+            str.push_str(&format!(
+                "{{ \"Opt\": {} }}",
+                print_type(s, recs, &NO_COMPOSITE, docs)
+            ));
         }
         Type::Vec(s) => {
-            str.push_str(&format!("{{ \"Vec\": {} }}", print_type(s)));
+            str.push_str(&format!(
+                "{{ \"Vec\": {} }}",
+                print_type(s, recs, &NO_COMPOSITE, docs)
+            ));
         }
         Type::Var(s) => {
-            str.push_str(&format!("{{ \"Var\": \"{s}\" }}"));
+            if recs.contains(s) {
+                str.push_str(&format!("{{ \"Rec\": \"{s}\" }}"));
+            } else {
+                str.push_str(&format!("{{ \"Var\": \"{s}\" }}"));
+            }
         }
         Type::Record(r) => {
             if is_tuple(ty) {
                 str.push_str("{ \"Tuple\":");
                 // str.push_str(&print_array(r));
-                str.push_str(&print_array(&r.iter().map(|f| f.ty.clone()).collect()));
+                str.push_str(&print_array(
+                    &r.iter().map(|f| f.ty.clone()).collect(),
+                    recs,
+                ));
                 str.push('}');
 
                 // str.push_str(&format!("{{ \"Tuple\": {} }}", print_fields(r)));
             } else {
-                str.push_str(&format!("{{ \"Record\": {{ {} }} }}", print_fields(r)));
+                str.push_str(&format!(
+                    "{{ \"Record\": {{ {} }}, \"hashes\": {{ {} }} }}",
+                    print_fields(r, recs, docs),
+                    print_hashes(r)
+                ));
             }
         }
         Type::Variant(r) => {
-            str.push_str(&format!("{{ \"Variant\": {{ {} }} }}", print_fields(r)));
+            str.push_str(&format!(
+                "{{ \"Variant\": {{ {} }}, \"hashes\": {{ {} }} }}",
+                print_fields(r, recs, docs),
+                print_hashes(r)
+            ));
         }
         Type::Service(s) => {
             str.push_str("{ \"Service\": {");
             let mut first = true;
 
-            for p in s {
+            for (name, ty) in s {
                 if !first {
                     str.push(',');
                 }
                 first = false;
-                str.push_str(&format!("\"{}\": {}", p.0, print_type(&p.1)));
+                let method = match ty {
+                    Type::Func(f) => print_func(f, Mode::of_method(name, f, composite), recs),
+                    _ => print_type(ty, recs, &NO_COMPOSITE, None),
+                };
+                let method = with_doc(method, docs.and_then(|x| x.get(name)));
+                str.push_str(&format!("{}: {method}", json_string(name)));
             }
 
             str.push_str("}}");
         }
-        Type::Func(f) => {
-            str.push_str(&format!(
-                "{{ \"Func\": {{ \"args\": {}, \"rets\": {}, \"modes\": {} }} }}",
-                print_array(&f.args),
-                print_array(&f.rets),
-                print_modes(&f.modes)
-            ));
-        }
-        Type::Empty | Type::Reserved | Type::Unknown => {}
+        Type::Func(f) => str.push_str(&print_func(f, Mode::of(f), recs)),
+        Type::Empty | Type::Reserved => str.push_str(&format!("\"{ty}\"")),
+        Type::Unknown => {}
+        // Knots only come from Rust-derived types, their display is the resolved type name
         Type::Knot(f) => {
-            str.push_str(&format!("{{ \"Knot\": {f} }}"));
+            str.push_str(&format!("{{ \"Rec\": \"{f}\" }}"));
         }
         Type::Class(_args, b) => {
             str.push_str("\"Init\": ");
-            str.push_str(&print_array(_args));
+            str.push_str(&print_array(_args, recs));
             str.push_str(", \"Spec\": ");
-            str.push_str(&print_type(b));
+            str.push_str(&print_type(b, recs, composite, docs));
 
             // str.push_str(&format!("\"Not handled, {}\"", ty))
         } // _ => {
@@ -146,7 +201,17 @@ pub fn print_type(ty: &Type) -> String {
     str
 }
 
-pub fn print_fields(fields: &Vec<Field>) -> String {
+pub fn print_func(f: &Function, mode: Mode, recs: &BTreeSet<String>) -> String {
+    format!(
+        "{{ \"Func\": {{ \"args\": {}, \"rets\": {}, \"modes\": {}, \"mode\": \"{}\" }} }}",
+        print_array(&f.args, recs),
+        print_array(&f.rets, recs),
+        print_modes(mode),
+        mode.name()
+    )
+}
+
+pub fn print_fields(fields: &Vec<Field>, recs: &BTreeSet<String>, docs: FieldDocs) -> String {
     let mut str = String::new();
     let mut first = true;
 
@@ -156,16 +221,161 @@ pub fn print_fields(fields: &Vec<Field>) -> String {
         }
         first = false;
 
-        str.push_str(&format!("\"{}\": {}", f.id, &print_type(&f.ty)));
+        let node = docs.and_then(|x| x.get(&f.id.to_string()));
+        let ty = print_type(&f.ty, recs, &NO_COMPOSITE, node.map(|x| &x.fields));
+        let label = json_string(&f.id.to_string());
+        str.push_str(&format!("{label}: {}", with_doc(ty, node)));
     }
 
     str
 }
 
-pub fn compile(env: &TypeEnv, actor: &Option<Type>) -> String {
+/// Wire id of every field, keyed by the label used in `print_fields`.
+pub fn print_hashes(fields: &[Field]) -> String {
+    let mut str = String::new();
+    let mut first = true;
+
+    for f in fields {
+        if !first {
+            str.push(',');
+        }
+        first = false;
+
+        let label = json_string(&f.id.to_string());
+        str.push_str(&format!("{label}: {}", f.id.get_id()));
+    }
+
+    str
+}
+
+/// Collects the names behind every named record and variant label.
+fn collect_labels(ty: &Type, labels: &mut BTreeMap<u32, String>) {
+    match ty {
+        Type::Opt(t) | Type::Vec(t) => collect_labels(t, labels),
+        Type::Record(fs) | Type::Variant(fs) => {
+            for f in fs {
+                if let Label::Named(name) = &f.id {
+                    labels.entry(f.id.get_id()).or_insert_with(|| name.clone());
+                }
+                collect_labels(&f.ty, labels);
+            }
+        }
+        Type::Func(f) => {
+            for t in f.args.iter().chain(f.rets.iter()) {
+                collect_labels(t, labels);
+            }
+        }
+        Type::Service(ms) => {
+            for (_, t) in ms {
+                collect_labels(t, labels);
+            }
+        }
+        Type::Class(args, t) => {
+            for a in args {
+                collect_labels(a, labels);
+            }
+            collect_labels(t, labels);
+        }
+        _ => {}
+    }
+}
+
+fn collect_vars<'a>(ty: &'a Type, vars: &mut Vec<&'a str>) {
+    match ty {
+        Type::Var(s) => vars.push(s),
+        Type::Opt(t) | Type::Vec(t) => collect_vars(t, vars),
+        Type::Record(fs) | Type::Variant(fs) => {
+            for f in fs {
+                collect_vars(&f.ty, vars);
+            }
+        }
+        Type::Func(f) => {
+            for t in f.args.iter().chain(f.rets.iter()) {
+                collect_vars(t, vars);
+            }
+        }
+        Type::Service(ms) => {
+            for (_, t) in ms {
+                collect_vars(t, vars);
+            }
+        }
+        Type::Class(args, t) => {
+            for a in args {
+                collect_vars(a, vars);
+            }
+            collect_vars(t, vars);
+        }
+        _ => {}
+    }
+}
+
+/// Names of all types that can reach themselves through `Var` references,
+/// this covers self recursion as well as mutually recursive groups.
+pub fn find_recursive(env: &TypeEnv) -> BTreeSet<String> {
+    let mut recs = BTreeSet::new();
+
+    for name in env.0.keys() {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![name.as_str()];
+
+        while let Some(current) = stack.pop() {
+            let Some(ty) = env.0.get(current) else {
+                continue;
+            };
+
+            let mut vars = Vec::new();
+            collect_vars(ty, &mut vars);
+
+            for v in vars {
+                if v == name {
+                    recs.insert(name.clone());
+                    stack.clear();
+                    break;
+                }
+                if seen.insert(v) {
+                    stack.push(v);
+                }
+            }
+        }
+    }
+
+    recs
+}
+
+pub fn compile(env: &TypeEnv, actor: &Option<Type>, docs: &Docs, composite: &Composite) -> String {
     let mut result = String::new();
+    let recs = find_recursive(env);
+
+    result.push_str("{ \"recs\": [");
+    let mut first = true;
+
+    for name in recs.iter() {
+        if !first {
+            result.push(',');
+        }
+        first = false;
+
+        result.push_str(&format!("\"{name}\""));
+    }
+
+    result.push_str("], \"hashes\": {");
+
+    let mut labels = BTreeMap::new();
+    for ty in env.0.values().chain(actor.iter()) {
+        collect_labels(ty, &mut labels);
+    }
+
+    let mut first = true;
+    for (hash, name) in labels.iter() {
+        if !first {
+            result.push(',');
+        }
+        first = false;
+
+        result.push_str(&format!("\"{hash}\": {}", json_string(name)));
+    }
 
-    result.push_str("{ \"types\": {");
+    result.push_str("}, \"types\": {");
     let mut first = true;
 
     for i in env.0.iter() {
@@ -174,7 +384,9 @@ pub fn compile(env: &TypeEnv, actor: &Option<Type>) -> String {
         }
         first = false;
 
-        result.push_str(&format!("\"{}\": {}", i.0, &print_type(i.1)));
+        let node = docs.types.get(i.0);
+        let ty = print_type(i.1, &recs, composite.of_type(i.0), node.map(|x| &x.fields));
+        result.push_str(&format!("\"{}\": {}", i.0, with_doc(ty, node)));
 
         // match i.1 {
         //     Type::Service(_) => {
@@ -189,14 +401,18 @@ pub fn compile(env: &TypeEnv, actor: &Option<Type>) -> String {
     result.push_str("}, \"actor\": {");
     // result.push('}');
 
+    let composite = composite.of_actor(env, actor);
     match actor {
         None => {}
         Some(actor) => match actor {
-            Type::Service(_) => {
-                result.push_str(&format!("\"Spec\": {}", &print_type(actor)));
+            Type::Service(_) | Type::Var(_) => {
+                result.push_str(&format!(
+                    "\"Spec\": {}",
+                    &print_type(actor, &recs, composite, Some(&docs.methods))
+                ));
             }
             _ => {
-                result.push_str(&print_type(actor));
+                result.push_str(&print_type(actor, &recs, composite, Some(&docs.methods)));
             }
         },
     }
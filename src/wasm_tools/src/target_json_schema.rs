@@ -0,0 +1,205 @@
+//! JSON Schema and OpenAPI documents for Candid services.
+//!
+//! Values use the JSON mapping of a JSON-over-HTTP proxy:
+//!  * `nat`, `int`, `nat64` and `int64` are decimal strings, as they do not fit
+//!    into a JSON number, smaller integers are numbers.
+//!  * `blob` is a base64 string, `principal` and `service` are textual principals.
+//!  * `opt T` is `T` or `null`, a variant is an object with a single case.
+//!  * Tuples and method arguments are arrays.
+
+use std::collections::BTreeSet;
+
+use candid::{
+    types::{Field, Function, Type},
+    TypeEnv,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{modes::Mode, target_json::is_tuple};
+
+/// Where named types live, `$defs` for JSON Schema and `components` for
+/// OpenAPI.
+pub struct Refs(&'static str);
+
+pub const JSON_SCHEMA_REFS: Refs = Refs("#/$defs/");
+pub const OPENAPI_REFS: Refs = Refs("#/components/schemas/");
+
+fn string_number(pattern: &str, format: &str) -> Value {
+    json!({ "type": "string", "pattern": pattern, "format": format })
+}
+
+fn integer(min: i64, max: u64) -> Value {
+    json!({ "type": "integer", "minimum": min, "maximum": max })
+}
+
+pub fn schema(env: &TypeEnv, ty: &Type, refs: &Refs) -> Value {
+    match ty {
+        Type::Null => json!({ "type": "null" }),
+        Type::Bool => json!({ "type": "boolean" }),
+        Type::Nat => string_number("^[0-9]+$", "nat"),
+        Type::Int => string_number("^-?[0-9]+$", "int"),
+        Type::Nat64 => string_number("^[0-9]+$", "nat64"),
+        Type::Int64 => string_number("^-?[0-9]+$", "int64"),
+        Type::Nat8 => integer(0, u8::MAX as u64),
+        Type::Nat16 => integer(0, u16::MAX as u64),
+        Type::Nat32 => integer(0, u32::MAX as u64),
+        Type::Int8 => integer(i8::MIN as i64, i8::MAX as u64),
+        Type::Int16 => integer(i16::MIN as i64, i16::MAX as u64),
+        Type::Int32 => integer(i32::MIN as i64, i32::MAX as u64),
+        Type::Float32 | Type::Float64 => json!({ "type": "number" }),
+        Type::Text => json!({ "type": "string" }),
+        Type::Principal | Type::Service(_) => json!({ "type": "string", "format": "principal" }),
+        Type::Reserved => json!({}),
+        Type::Empty | Type::Unknown => json!({ "not": {} }),
+        Type::Opt(t) => json!({ "anyOf": [schema(env, t, refs), { "type": "null" }] }),
+        Type::Vec(t) if **t == Type::Nat8 => {
+            json!({ "type": "string", "contentEncoding": "base64" })
+        }
+        Type::Vec(t) => json!({ "type": "array", "items": schema(env, t, refs) }),
+        Type::Var(name) => json!({ "$ref": format!("{}{name}", refs.0) }),
+        Type::Knot(id) => json!({ "$ref": format!("{}{id}", refs.0) }),
+        Type::Record(fs) if is_tuple(ty) => tuple(
+            env,
+            &fs.iter().map(|f| f.ty.clone()).collect::<Vec<_>>(),
+            refs,
+        ),
+        Type::Record(fs) => record(env, fs, refs),
+        Type::Variant(fs) => json!({
+            "oneOf": fs.iter().map(|f| {
+                json!({
+                    "type": "object",
+                    "properties": { f.id.to_string(): schema(env, &f.ty, refs) },
+                    "required": [f.id.to_string()],
+                    "additionalProperties": false,
+                })
+            }).collect::<Vec<_>>()
+        }),
+        Type::Func(_) => json!({
+            "type": "array",
+            "prefixItems": [{ "type": "string", "format": "principal" }, { "type": "string" }],
+            "minItems": 2,
+            "maxItems": 2,
+        }),
+        Type::Class(_, t) => schema(env, t, refs),
+    }
+}
+
+fn record(env: &TypeEnv, fs: &[Field], refs: &Refs) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for f in fs {
+        let name = f.id.to_string();
+        // Aliases of optional types can be left out as well
+        let ty = env.trace_type(&f.ty).unwrap_or_else(|_| f.ty.clone());
+        if !matches!(ty, Type::Opt(_) | Type::Null | Type::Reserved) {
+            required.push(Value::String(name.clone()));
+        }
+        properties.insert(name, schema(env, &f.ty, refs));
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn tuple(env: &TypeEnv, tys: &[Type], refs: &Refs) -> Value {
+    json!({
+        "type": "array",
+        "prefixItems": tys.iter().map(|t| schema(env, t, refs)).collect::<Vec<_>>(),
+        "minItems": tys.len(),
+        "maxItems": tys.len(),
+    })
+}
+
+fn definitions(env: &TypeEnv, refs: &Refs) -> Map<String, Value> {
+    env.0
+        .iter()
+        .map(|(name, ty)| (name.clone(), schema(env, ty, refs)))
+        .collect()
+}
+
+fn methods<'a>(env: &'a TypeEnv, actor: &'a Option<Type>) -> Vec<(&'a String, &'a Function)> {
+    let Some(Ok(service)) = actor.as_ref().map(|x| env.as_service(x)) else {
+        return Vec::new();
+    };
+
+    service
+        .iter()
+        .filter_map(|(name, ty)| env.as_func(ty).ok().map(|f| (name, f)))
+        .collect()
+}
+
+/// JSON Schema with the types under `$defs` and the argument and result
+/// arrays of every method. `composite` holds the composite queries of the
+/// actor.
+pub fn compile(env: &TypeEnv, actor: &Option<Type>, composite: &BTreeSet<String>) -> Value {
+    let refs = JSON_SCHEMA_REFS;
+
+    let methods: Map<String, Value> = methods(env, actor)
+        .into_iter()
+        .map(|(name, func)| {
+            let method = json!({
+                "mode": Mode::of_method(name, func, composite).name(),
+                "args": tuple(env, &func.args, &refs),
+                "result": tuple(env, &func.rets, &refs),
+            });
+            (name.clone(), method)
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$defs": definitions(env, &refs),
+        "methods": methods,
+    })
+}
+
+/// OpenAPI document of a proxy that takes the arguments as a JSON array posted
+/// to `/{method}` and replies with the results as a JSON array.
+pub fn compile_openapi(
+    env: &TypeEnv,
+    actor: &Option<Type>,
+    composite: &BTreeSet<String>,
+    title: &str,
+) -> Value {
+    let refs = OPENAPI_REFS;
+
+    let paths: Map<String, Value> = methods(env, actor)
+        .into_iter()
+        .map(|(name, func)| {
+            let mode = Mode::of_method(name, func, composite);
+            let mut ok = json!({ "description": "Method reply" });
+            if mode != Mode::Oneway {
+                ok["content"] =
+                    json!({ "application/json": { "schema": tuple(env, &func.rets, &refs) } });
+            }
+
+            let path = json!({
+                "post": {
+                    "operationId": name,
+                    "tags": [mode.name()],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": tuple(env, &func.args, &refs) } },
+                    },
+                    "responses": {
+                        "200": ok,
+                        "default": { "description": "Call was rejected or the canister trapped" },
+                    },
+                }
+            });
+            (format!("/{name}"), path)
+        })
+        .collect();
+
+    json!({
+        "openapi": "3.1.0",
+        "info": { "title": title, "version": "1.0.0" },
+        "paths": paths,
+        "components": { "schemas": definitions(env, &refs) },
+    })
+}
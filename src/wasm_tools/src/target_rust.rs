@@ -0,0 +1,485 @@
+//! Rust bindings for Candid interfaces.
+//!
+//! Adapted from `candid::bindings::rust`, with a [`Config`] so the output can
+//! be used directly as ic-cdk client stubs in another canister. Upstream has no
+//! configuration to wrap, and panics where this returns an error.
+
+use std::collections::BTreeSet;
+
+use candid::{
+    bindings::analysis::{chase_actor, infer_rec},
+    pretty::{concat, enclose, enclose_space, kwd, lines, str, LINE_WIDTH},
+    types::{Field, Function, Label, Type},
+    TypeEnv,
+};
+use pretty::RcDoc;
+use serde::Deserialize;
+
+type RecPoints<'a> = BTreeSet<&'a str>;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Path the generated code imports candid from
+    pub candid_crate: String,
+    /// Attributes put in front of every generated struct and enum
+    pub type_attributes: String,
+    /// Name of the generated client struct
+    pub service_name: String,
+    /// Make generated types, their fields and the client struct public
+    pub public: bool,
+    /// Generate the client struct with an async method per service method
+    pub stubs: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            candid_crate: "ic_cdk::export::candid".to_string(),
+            type_attributes: "#[derive(CandidType, Deserialize)]".to_string(),
+            service_name: "SERVICE".to_string(),
+            public: false,
+            stubs: true,
+        }
+    }
+}
+
+impl Config {
+    fn vis(&self) -> &'static str {
+        if self.public {
+            "pub "
+        } else {
+            ""
+        }
+    }
+}
+
+// The definition of tuple is language specific.
+fn is_tuple(fs: &[Field]) -> bool {
+    if fs.is_empty() {
+        return false;
+    }
+    !fs.iter()
+        .enumerate()
+        .any(|(i, field)| field.id.get_id() != (i as u32))
+}
+
+static KEYWORDS: [&str; 51] = [
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+
+fn ident_(id: &str) -> (RcDoc<'_>, bool) {
+    if id.is_empty()
+        || id.starts_with(|c: char| !c.is_ascii_alphabetic() && c != '_')
+        || id.chars().any(|c| !c.is_ascii_alphanumeric() && c != '_')
+    {
+        (
+            RcDoc::as_string(format!("_{}_", candid::idl_hash(id))),
+            true,
+        )
+    } else if ["crate", "self", "super", "Self"].contains(&id) {
+        (str(id).append("_"), true)
+    } else if KEYWORDS.contains(&id) {
+        (str("r#").append(id), false)
+    } else {
+        (str(id), false)
+    }
+}
+
+fn ident(id: &str) -> RcDoc<'_> {
+    ident_(id).0
+}
+
+/// Visibility goes after the rename attribute
+fn field_name<'a>(id: &'a str, vis: &'static str) -> RcDoc<'a> {
+    let (doc, is_rename) = ident_(id);
+    if is_rename {
+        str("#[serde(rename=\"")
+            .append(id.escape_debug().to_string())
+            .append("\")]")
+            .append(RcDoc::line())
+            .append(vis)
+            .append(doc)
+    } else {
+        str(vis).append(doc)
+    }
+}
+
+fn pp_ty<'a>(ty: &'a Type, recs: &RecPoints) -> Result<RcDoc<'a>, String> {
+    use Type::*;
+    Ok(match *ty {
+        Null => str("()"),
+        Bool => str("bool"),
+        Nat => str("candid::Nat"),
+        Int => str("candid::Int"),
+        Nat8 => str("u8"),
+        Nat16 => str("u16"),
+        Nat32 => str("u32"),
+        Nat64 => str("u64"),
+        Int8 => str("i8"),
+        Int16 => str("i16"),
+        Int32 => str("i32"),
+        Int64 => str("i64"),
+        Float32 => str("f32"),
+        Float64 => str("f64"),
+        Text => str("String"),
+        Reserved => str("candid::Reserved"),
+        Empty => str("candid::Empty"),
+        Var(ref id) => {
+            let name = ident(id);
+            if recs.contains(id.as_str()) {
+                str("Box<").append(name).append(">")
+            } else {
+                name
+            }
+        }
+        Principal => str("candid::Principal"),
+        Opt(ref t) => str("Option").append(enclose("<", pp_ty(t, recs)?, ">")),
+        Vec(ref t) => str("Vec").append(enclose("<", pp_ty(t, recs)?, ">")),
+        Record(ref fs) => pp_record_fields(fs, recs, "")?,
+        Func(_) => str("candid::Func"),
+        Service(_) => str("candid::Service"),
+        // Variants are named by nominalize, the rest cannot be a value type
+        Variant(_) | Class(_, _) | Knot(_) | Unknown => {
+            return Err(format!("Cannot generate a Rust type for {ty}"))
+        }
+    })
+}
+
+fn pp_label<'a>(id: &'a Label, vis: &'static str) -> RcDoc<'a> {
+    match id {
+        Label::Named(str) => field_name(str, vis),
+        Label::Id(n) | Label::Unnamed(n) => {
+            str(vis).append("_").append(RcDoc::as_string(n)).append("_")
+        }
+    }
+}
+
+fn pp_record_fields<'a>(
+    fs: &'a [Field],
+    recs: &RecPoints,
+    vis: &'static str,
+) -> Result<RcDoc<'a>, String> {
+    if is_tuple(fs) {
+        let tuple = fs
+            .iter()
+            .map(|f| Ok(str(vis).append(pp_ty(&f.ty, recs)?).append(",")))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(enclose("(", RcDoc::concat(tuple), ")"))
+    } else {
+        let fields = fs
+            .iter()
+            .map(|f| {
+                Ok(pp_label(&f.id, vis)
+                    .append(kwd(":"))
+                    .append(pp_ty(&f.ty, recs)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(enclose_space("{", concat(fields.into_iter(), ","), "}"))
+    }
+}
+
+fn pp_variant_field<'a>(field: &'a Field, recs: &RecPoints) -> Result<RcDoc<'a>, String> {
+    Ok(match &field.ty {
+        Type::Null => pp_label(&field.id, ""),
+        Type::Record(fs) => pp_label(&field.id, "").append(pp_record_fields(fs, recs, "")?),
+        _ => pp_label(&field.id, "").append(enclose("(", pp_ty(&field.ty, recs)?, ")")),
+    })
+}
+
+fn pp_variant_fields<'a>(fs: &'a [Field], recs: &RecPoints) -> Result<RcDoc<'a>, String> {
+    let fields = fs
+        .iter()
+        .map(|f| pp_variant_field(f, recs))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(enclose_space("{", concat(fields.into_iter(), ","), "}"))
+}
+
+fn pp_defs<'a>(
+    env: &'a TypeEnv,
+    def_list: &'a [&'a str],
+    recs: &'a RecPoints,
+    config: &Config,
+) -> Result<RcDoc<'a>, String> {
+    let vis = config.vis();
+    let defs = def_list.iter().map(|id| {
+        let derive = RcDoc::text(config.type_attributes.clone());
+        let ty = env.find_type(id).map_err(|x| format!("{x}"))?;
+        let name = ident(id).append(" ");
+        Ok(match ty {
+            Type::Record(fs) => {
+                let separator = if is_tuple(fs) {
+                    RcDoc::text(";")
+                } else {
+                    RcDoc::nil()
+                };
+                derive
+                    .append(RcDoc::line())
+                    .append(vis)
+                    .append("struct ")
+                    .append(name)
+                    .append(pp_record_fields(fs, recs, vis)?)
+                    .append(separator)
+                    .append(RcDoc::hardline())
+            }
+            Type::Variant(fs) => derive
+                .append(RcDoc::line())
+                .append(vis)
+                .append("enum ")
+                .append(name)
+                .append(pp_variant_fields(fs, recs)?)
+                .append(RcDoc::hardline()),
+            _ => {
+                if recs.contains(id) {
+                    derive
+                        .append(RcDoc::line())
+                        .append(vis)
+                        .append("struct ")
+                        .append(ident(id))
+                        .append(enclose("(", str(vis).append(pp_ty(ty, recs)?), ")"))
+                        .append(";")
+                        .append(RcDoc::hardline())
+                } else {
+                    str(vis)
+                        .append(kwd("type"))
+                        .append(name)
+                        .append("= ")
+                        .append(pp_ty(ty, recs)?)
+                        .append(";")
+                }
+            }
+        })
+    });
+    Ok(lines(defs.collect::<Result<Vec<_>, String>>()?.into_iter()))
+}
+
+fn pp_function<'a>(id: &'a str, func: &'a Function) -> Result<RcDoc<'a>, String> {
+    let name = ident(id);
+    let empty = BTreeSet::new();
+    let args = func
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| Ok(RcDoc::as_string(format!("arg{i}: ")).append(pp_ty(ty, &empty)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let args = concat(std::iter::once(str("&self")).chain(args), ",");
+    let rets = func
+        .rets
+        .iter()
+        .map(|ty| Ok(pp_ty(ty, &empty)?.append(",")))
+        .collect::<Result<Vec<_>, String>>()?;
+    let rets = enclose("(", RcDoc::concat(rets), ")");
+    let sig = kwd("pub async fn")
+        .append(name)
+        .append(enclose("(", args, ")"))
+        .append(kwd(" ->"))
+        .append(enclose("CallResult<", rets, "> "));
+    let args = RcDoc::concat((0..func.args.len()).map(|i| RcDoc::text(format!("arg{i},"))));
+    let method = id.escape_debug().to_string();
+    let body = str("ic_cdk::call(self.0, \"")
+        .append(method)
+        .append("\", ")
+        .append(enclose("(", args, ")"))
+        .append(").await");
+    Ok(sig.append(enclose_space("{", body, "}")))
+}
+
+fn pp_actor<'a>(env: &'a TypeEnv, actor: &'a Type, config: &Config) -> Result<RcDoc<'a>, String> {
+    let serv = env.as_service(actor).map_err(|x| format!("{x}"))?;
+    let functions = serv
+        .iter()
+        .map(|(id, func)| pp_function(id, env.as_func(func).map_err(|x| format!("{x}"))?))
+        .collect::<Result<Vec<_>, String>>()?;
+    let body = RcDoc::intersperse(functions, RcDoc::hardline());
+    let vis = config.vis();
+    Ok(RcDoc::text(format!(
+        "{vis}struct {}({vis}candid::Principal);",
+        config.service_name
+    ))
+    .append(RcDoc::hardline())
+    .append(RcDoc::text(format!("impl {}", config.service_name)))
+    .append(enclose_space("{", body, "}")))
+}
+
+pub fn compile(env: &TypeEnv, actor: &Option<Type>, config: &Config) -> Result<String, String> {
+    let mut header = format!(
+        "// This is a generated Rust binding from Candid.\n\
+         use {}::{{self, CandidType, Deserialize}};\n",
+        config.candid_crate
+    );
+    if config.stubs && actor.is_some() {
+        header.push_str("use ic_cdk::api::call::CallResult;\n");
+    }
+
+    let (env, actor) = nominalize_all(env, actor);
+    let def_list: Vec<_> = if let Some(actor) = &actor {
+        chase_actor(&env, actor).map_err(|x| format!("{x}"))?
+    } else {
+        env.0.iter().map(|pair| pair.0.as_ref()).collect()
+    };
+    let recs = infer_rec(&env, &def_list).map_err(|x| format!("{x}"))?;
+    let defs = pp_defs(&env, &def_list, &recs, config)?;
+    let doc = match &actor {
+        Some(actor) if config.stubs => defs.append(pp_actor(&env, actor, config)?),
+        _ => defs,
+    };
+    let doc = RcDoc::text(header).append(RcDoc::line()).append(doc);
+    Ok(doc.pretty(LINE_WIDTH).to_string())
+}
+
+enum TypePath {
+    Id(String),
+    Opt,
+    Vec,
+    RecordField(String),
+    VariantField(String),
+    Func(String),
+    Init,
+}
+
+fn path_to_var(path: &[TypePath]) -> String {
+    let name: Vec<&str> = path
+        .iter()
+        .map(|node| match node {
+            TypePath::Id(id) => id.as_str(),
+            TypePath::RecordField(f) | TypePath::VariantField(f) => f.as_str(),
+            TypePath::Opt => "inner",
+            TypePath::Vec => "item",
+            TypePath::Func(id) => id.as_str(),
+            TypePath::Init => "init",
+        })
+        .collect();
+    name.join("_")
+}
+
+// Convert structural typing to nominal typing to fit Rust's type system
+fn nominalize(env: &mut TypeEnv, path: &mut Vec<TypePath>, t: Type) -> Type {
+    match t {
+        Type::Opt(ty) => {
+            path.push(TypePath::Opt);
+            let ty = nominalize(env, path, *ty);
+            path.pop();
+            Type::Opt(Box::new(ty))
+        }
+        Type::Vec(ty) => {
+            path.push(TypePath::Vec);
+            let ty = nominalize(env, path, *ty);
+            path.pop();
+            Type::Vec(Box::new(ty))
+        }
+        Type::Record(fs) => {
+            if matches!(
+                path.last(),
+                None | Some(TypePath::VariantField(_)) | Some(TypePath::Id(_))
+            ) || is_tuple(&fs)
+            {
+                let fs: Vec<_> = fs
+                    .into_iter()
+                    .map(|Field { id, ty }| {
+                        path.push(TypePath::RecordField(id.to_string()));
+                        let ty = nominalize(env, path, ty);
+                        path.pop();
+                        Field { id, ty }
+                    })
+                    .collect();
+                Type::Record(fs)
+            } else {
+                let new_var = path_to_var(path);
+                let ty = nominalize(
+                    env,
+                    &mut vec![TypePath::Id(new_var.clone())],
+                    Type::Record(fs),
+                );
+                env.0.insert(new_var.clone(), ty);
+                Type::Var(new_var)
+            }
+        }
+        Type::Variant(fs) => match path.last() {
+            None | Some(TypePath::Id(_)) => {
+                let fs: Vec<_> = fs
+                    .into_iter()
+                    .map(|Field { id, ty }| {
+                        path.push(TypePath::VariantField(id.to_string()));
+                        let ty = nominalize(env, path, ty);
+                        path.pop();
+                        Field { id, ty }
+                    })
+                    .collect();
+                Type::Variant(fs)
+            }
+            Some(_) => {
+                let new_var = path_to_var(path);
+                let ty = nominalize(
+                    env,
+                    &mut vec![TypePath::Id(new_var.clone())],
+                    Type::Variant(fs),
+                );
+                env.0.insert(new_var.clone(), ty);
+                Type::Var(new_var)
+            }
+        },
+        Type::Func(func) => Type::Func(Function {
+            modes: func.modes,
+            args: func
+                .args
+                .into_iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    path.push(TypePath::Func(format!("arg{i}")));
+                    let ty = nominalize(env, path, ty);
+                    path.pop();
+                    ty
+                })
+                .collect(),
+            rets: func
+                .rets
+                .into_iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    path.push(TypePath::Func(format!("ret{i}")));
+                    let ty = nominalize(env, path, ty);
+                    path.pop();
+                    ty
+                })
+                .collect(),
+        }),
+        Type::Service(serv) => Type::Service(
+            serv.into_iter()
+                .map(|(meth, ty)| {
+                    path.push(TypePath::Id(meth.to_string()));
+                    let ty = nominalize(env, path, ty);
+                    path.pop();
+                    (meth, ty)
+                })
+                .collect(),
+        ),
+        Type::Class(args, ty) => Type::Class(
+            args.into_iter()
+                .map(|ty| {
+                    path.push(TypePath::Init);
+                    let ty = nominalize(env, path, ty);
+                    path.pop();
+                    ty
+                })
+                .collect(),
+            Box::new(nominalize(env, path, *ty)),
+        ),
+        _ => t,
+    }
+}
+
+fn nominalize_all(env: &TypeEnv, actor: &Option<Type>) -> (TypeEnv, Option<Type>) {
+    let mut res = TypeEnv(Default::default());
+    for (id, ty) in env.0.iter() {
+        let ty = nominalize(&mut res, &mut vec![TypePath::Id(id.clone())], ty.clone());
+        res.0.insert(id.to_string(), ty);
+    }
+    let actor = actor
+        .as_ref()
+        .map(|ty| nominalize(&mut res, &mut vec![], ty.clone()));
+    (res, actor)
+}
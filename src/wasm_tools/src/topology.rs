@@ -0,0 +1,159 @@
+//! Subnets of a multi-subnet replica.
+//!
+//! Subnet `0` is the root (NNS) subnet, its key is the root key and it needs no
+//! delegation. Subnet `i` holds the canister ids `i * CANISTERS_PER_SUBNET` up
+//! to the next subnet, like the ranges of mainnet subnets.
+
+use ciborium::value::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    certificate::{self, DER_PREFIX},
+    hash_tree::{domain_sep, encode_cbor, sha256, LabeledTree},
+    principal::{canister_id, canister_number, self_authenticating},
+};
+
+pub const CANISTERS_PER_SUBNET: u64 = 1 << 20;
+
+/// First and last canister id of the subnet.
+pub fn canister_range(subnet: u64) -> (Vec<u8>, Vec<u8>) {
+    let start = subnet * CANISTERS_PER_SUBNET;
+    (
+        canister_id(start),
+        canister_id(start + CANISTERS_PER_SUBNET - 1),
+    )
+}
+
+/// Self-authenticating principal of a DER public key, which subnet ids are.
+pub fn subnet_id(public_key_der: &[u8]) -> Vec<u8> {
+    self_authenticating(public_key_der)
+}
+
+/// Delegation map `{ subnet_id, certificate }` of the subnet, signed with the
+/// root secret key at `time`.
+pub fn delegation(
+    root_secret_key: &[u8],
+    subnet_public_key: &[u8],
+    subnet: u64,
+    time: &[u8],
+) -> Result<Value, String> {
+    let der = [&DER_PREFIX[..], certificate::public_key(subnet_public_key)?].concat();
+    let id = subnet_id(&der);
+    let (start, end) = canister_range(subnet);
+    let ranges = encode_cbor(Value::Array(vec![Value::Array(vec![
+        Value::Bytes(start),
+        Value::Bytes(end),
+    ])]))?;
+
+    let mut tree = LabeledTree::default();
+    let path = |label: &str| vec![b"subnet".to_vec(), id.clone(), label.as_bytes().to_vec()];
+    tree.insert(&path("canister_ranges"), ranges)?;
+    tree.insert(&path("public_key"), der.clone())?;
+    tree.insert(&[b"time".to_vec()], time.to_vec())?;
+
+    let cert = certificate::build(&tree.to_hash_tree(), root_secret_key, None)?;
+
+    Ok(Value::Map(vec![
+        (Value::Text("subnet_id".into()), Value::Bytes(id)),
+        (Value::Text("certificate".into()), Value::Bytes(cert)),
+    ]))
+}
+
+/// Seed of the BLS key pair of the subnet, derived from the root secret key so
+/// that a fixed root key gives fixed subnet keys.
+#[wasm_bindgen]
+pub fn subnet_seed(root_secret_key: &[u8], subnet: u64) -> Vec<u8> {
+    sha256(&[
+        &domain_sep("lightic-subnet"),
+        root_secret_key,
+        &subnet.to_be_bytes(),
+    ])
+    .to_vec()
+}
+
+/// Subnet the canister belongs to, none for principals that are not canister
+/// ids.
+#[wasm_bindgen]
+pub fn subnet_of_canister(canister_id: &[u8]) -> Option<u64> {
+    canister_number(canister_id).map(|n| n / CANISTERS_PER_SUBNET)
+}
+
+/// CBOR of the delegation of the subnet. `time` is the LEB128 encoded time of
+/// the delegation certificate.
+#[wasm_bindgen]
+pub fn subnet_delegation(
+    root_secret_key: &[u8],
+    subnet_public_key: &[u8],
+    subnet: u64,
+    time: &[u8],
+) -> Result<Vec<u8>, String> {
+    if subnet == 0 {
+        return Err("The root subnet has no delegation".to_string());
+    }
+
+    encode_cbor(delegation(
+        root_secret_key,
+        subnet_public_key,
+        subnet,
+        time,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bls::{bls_init, bls_key_pair_from_seed},
+        certificate::{verify_certificate, Certificate},
+        hash_tree::decode_cbor,
+    };
+
+    #[test]
+    fn canister_ranges() {
+        assert_eq!(canister_id(1), [0, 0, 0, 0, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(
+            canister_range(1),
+            (
+                vec![0, 0, 0, 0, 0, 0x10, 0, 0, 1, 1],
+                vec![0, 0, 0, 0, 0, 0x1f, 0xff, 0xff, 1, 1]
+            )
+        );
+        assert_eq!(subnet_of_canister(&canister_id(5)), Some(0));
+        assert_eq!(subnet_of_canister(&canister_id(3 << 20)), Some(3));
+        assert_eq!(subnet_of_canister(&[4]), None);
+    }
+
+    #[test]
+    fn delegated_certificate() -> Result<(), String> {
+        // 2023-11-14 in nanoseconds, LEB128 encoded
+        let time = [0x80, 0x80, 0xa8, 0xb1, 0xe3, 0x9f, 0xe7, 0xcb, 0x17];
+        let now = 1_700_000_000_000_000_000;
+
+        bls_init()?;
+        let root = bls_key_pair_from_seed(&[9u8; 32])?;
+        let subnet = bls_key_pair_from_seed(&subnet_seed(&root[..48], 2))?;
+
+        let delegation = subnet_delegation(&root[..48], &subnet[48..], 2, &time)?;
+        let Value::Map(fields) = decode_cbor(&delegation)? else {
+            return Err("delegation is not a map".to_string());
+        };
+        let der = [&DER_PREFIX[..], &subnet[48..]].concat();
+        assert_eq!(fields[0].1, Value::Bytes(subnet_id(&der)));
+
+        let mut tree = LabeledTree::default();
+        tree.insert(&[b"time".to_vec()], time.to_vec())?;
+        let cert = certificate::build(
+            &tree.to_hash_tree(),
+            &subnet[..48],
+            Some(decode_cbor(&delegation)?),
+        )?;
+        assert!(Certificate::decode(&cert)?.delegation.is_some());
+
+        verify_certificate(&cert, &root[48..], &canister_id((2 << 20) + 7), now)?;
+        assert!(verify_certificate(&cert, &root[48..], &canister_id(7), now).is_err());
+
+        assert!(subnet_delegation(&root[..48], &root[48..], 0, &time).is_err());
+
+        Ok(())
+    }
+}
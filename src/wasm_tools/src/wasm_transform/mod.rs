@@ -275,7 +275,7 @@ impl<'a> Module<'a> {
                         }
                         let item_reader = element.items.get_items_reader()?;
                         let items = item_reader.into_iter().collect::<Result<Vec<_>, _>>()?;
-                        let items = match items.get(0) {
+                        let items = match items.first() {
                             Some(ElementItem::Func(_)) => {
                                 let mut func_items = vec![];
                                 for item in items {
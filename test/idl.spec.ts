@@ -1,4 +1,5 @@
 import { IDL } from '@dfinity/candid'
+import { assert } from 'chai'
 
 import fs from 'fs'
 import { TestContext } from '../src'
@@ -12,4 +13,11 @@ describe('IDL Build', function () {
     const candid = JSON.parse(jsonCandid)
     buildIdl(IDL, candid)
   })
+  it('maps every primitive', function () {
+    const candidSpec = 'service : { f : (nat16, int8, int16, int32, int64, float32, float64, null, empty, reserved) -> () }'
+    const candid = JSON.parse(parse_candid(candidSpec))
+    const { idl } = buildIdl(IDL, candid)
+    const expected = [IDL.Nat16, IDL.Int8, IDL.Int16, IDL.Int32, IDL.Int64, IDL.Float32, IDL.Float64, IDL.Null, IDL.Empty, IDL.Reserved]
+    assert.deepEqual(idl._fields[0][1].argTypes, expected)
+  })
 })
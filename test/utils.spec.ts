@@ -1,5 +1,5 @@
 import { assert } from "chai";
-import { u64IntoCanisterId, u64IntoPrincipalId } from "../src/utils";
+import { canisterIdIntoU64, u64IntoCanisterId, u64IntoPrincipalId } from "../src/utils";
 
 describe('utils', function () {
     it('u64IntoPrincipalId' , async function () {
@@ -18,4 +18,10 @@ describe('utils', function () {
 
         assert.equal(printText, "rwlgt-iiaaa-aaaaa-aaaaa-cai");
     }); 
+
+    it('canisterIdIntoU64' , async function () {
+        for (const num of [0n, 1n, 256n, 1n << 20n]) {
+            assert.equal(canisterIdIntoU64(u64IntoCanisterId(num)), num);
+        }
+    });
 });
\ No newline at end of file